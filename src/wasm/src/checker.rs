@@ -0,0 +1,239 @@
+use crate::judge::Grade;
+
+// Offline answer checker: compares the player's sentence with the reference translations.
+// The pass threshold is Settings::pass_score, so ?pass= moves the verdict with the retry rule
+
+pub const CLOSE_SCORE: f64 = 50.0;
+const CHRF_ORDER: usize = 6;
+const CHRF_BETA: f64 = 2.0;
+
+// apostrophes are removed by sanitize, so contractions are matched without them
+
+const CONTRACTIONS: [(&str, &str); 16] = [
+    ("dont", "do not"),
+    ("doesnt", "does not"),
+    ("didnt", "did not"),
+    ("isnt", "is not"),
+    ("arent", "are not"),
+    ("wasnt", "was not"),
+    ("werent", "were not"),
+    ("cant", "can not"),
+    ("cannot", "can not"),
+    ("wont", "will not"),
+    ("wouldnt", "would not"),
+    ("havent", "have not"),
+    ("im", "i am"),
+    ("ive", "i have"),
+    ("youre", "you are"),
+    ("shes", "she is"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub reference: String,
+    pub score: f64,         // 0 - 100
+    pub chrf: f64,          // 0 - 100
+    pub edit_distance: usize,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+impl Check {
+    pub fn passed(&self, pass_score: u8) -> bool {
+        self.score >= pass_score as f64
+    }
+
+    pub fn grade(&self, pass_score: u8) -> Grade {
+        let mut _lines = vec![String::from(if self.passed(pass_score) {
+            "Well done."
+        } else if self.score >= CLOSE_SCORE {
+            "Almost."
+        } else {
+            "Try again."
+        })];
+        if !self.extra.is_empty() {
+            _lines.push(format!("Extra words: {}", self.extra.join(", ")));
+        }
+        Grade {
+            correct: self.passed(pass_score),
+            score: self.score.round().clamp(0.0, 100.0) as u8,
+            corrected_sentence: self.reference.clone(),
+            explanation: _lines.join("\n"),
+            missing: self.missing.clone(),
+        }
+    }
+}
+
+// lower case words without punctuation, contractions expanded
+
+pub fn normalize(text: &str) -> Vec<String> {
+    let _cleaned: String = text.chars()
+        .filter(|c| !matches!(c, '\'' | '’'))
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { ' ' })
+        .collect();
+    let mut _tokens = Vec::new();
+    for _word in _cleaned.split_whitespace() {
+        match CONTRACTIONS.iter().find(|(short, _)| *short == _word) {
+            Some((_, long)) => _tokens.extend(long.split(' ').map(String::from)),
+            None => _tokens.push(_word.to_string()),
+        }
+    }
+    _tokens
+}
+
+// Levenshtein distance over words
+
+pub fn edit_distance(a: &[String], b: &[String]) -> usize {
+    let mut _prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, _a) in a.iter().enumerate() {
+        let mut _row = vec![i + 1; b.len() + 1];
+        for (j, _b) in b.iter().enumerate() {
+            let _cost = if _a == _b { 0 } else { 1 };
+            _row[j + 1] = (_prev[j] + _cost).min(_prev[j + 1] + 1).min(_row[j] + 1);
+        }
+        _prev = _row;
+    }
+    _prev[b.len()]
+}
+
+// chrF: F-score of character n-grams (spaces removed), 0 - 100
+
+pub fn chrf(hypothesis: &[String], reference: &[String]) -> f64 {
+    let _h: Vec<char> = hypothesis.concat().chars().collect();
+    let _r: Vec<char> = reference.concat().chars().collect();
+    let mut _precision = 0.0;
+    let mut _recall = 0.0;
+    let mut _orders = 0.0;
+    for n in 1..=CHRF_ORDER {
+        if _h.len() < n || _r.len() < n { break; }
+        let mut _r_grams: Vec<&[char]> = _r.windows(n).collect();
+        let _h_count = _h.len() - n + 1;
+        let _r_count = _r_grams.len();
+        let mut _matched = 0;
+        for _gram in _h.windows(n) {
+            if let Some(_pos) = _r_grams.iter().position(|g| *g == _gram) {
+                _r_grams.swap_remove(_pos);
+                _matched += 1;
+            }
+        }
+        _precision += _matched as f64 / _h_count as f64;
+        _recall += _matched as f64 / _r_count as f64;
+        _orders += 1.0;
+    }
+    if _orders == 0.0 { return 0.0; }
+    let _p = _precision / _orders;
+    let _r = _recall / _orders;
+    if _p + _r == 0.0 { return 0.0; }
+    let _beta2 = CHRF_BETA * CHRF_BETA;
+    100.0 * (1.0 + _beta2) * _p * _r / (_beta2 * _p + _r)
+}
+
+fn difference(a: &[String], b: &[String]) -> Vec<String> {
+    let mut _rest: Vec<&String> = b.iter().collect();
+    let mut _missing = Vec::new();
+    for _word in a.iter() {
+        match _rest.iter().position(|w| *w == _word) {
+            Some(_pos) => { _rest.swap_remove(_pos); },
+            None => _missing.push(_word.clone()),
+        }
+    }
+    _missing
+}
+
+pub fn check(answer: &str, reference: &str) -> Check {
+    let _h = normalize(answer);
+    let _r = normalize(reference);
+    let _chrf = chrf(&_h, &_r);
+    let _distance = edit_distance(&_h, &_r);
+    let _longest = _h.len().max(_r.len()).max(1);
+    let _word_score = 100.0 * (1.0 - _distance as f64 / _longest as f64);
+    Check {
+        reference: reference.to_string(),
+        score: (_chrf + _word_score) / 2.0,
+        chrf: _chrf,
+        edit_distance: _distance,
+        missing: difference(&_r, &_h),
+        extra: difference(&_h, &_r),
+    }
+}
+
+// best match among the reference translations
+
+pub fn check_best(answer: &str, references: &[String]) -> Option<Check> {
+    references.iter()
+        .map(|reference| check(answer, reference))
+        .max_by(|a, b| a.score.total_cmp(&b.score))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::PASS_SCORE;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn normalize_expands_contractions_without_apostrophes() {
+        assert_eq!(normalize("Don't forget, I'm HERE!"), words("do not forget i am here"));
+        assert_eq!(normalize("Dont forget"), words("do not forget"));
+        assert_eq!(normalize("She cannot’"), words("she can not"));
+    }
+
+    #[test]
+    fn edit_distance_counts_words() {
+        let _reference = words("do not forget to say good morning");
+        assert_eq!(edit_distance(&_reference, &_reference), 0);
+        assert_eq!(edit_distance(&words("do not forget to say morning"), &_reference), 1);
+        assert_eq!(edit_distance(&words("do not forgot to say good evening"), &_reference), 2);
+        assert_eq!(edit_distance(&[], &_reference), _reference.len());
+    }
+
+    #[test]
+    fn chrf_is_high_for_near_misses_only() {
+        let _reference = words("good morning");
+        assert!((chrf(&_reference, &_reference) - 100.0).abs() < 1e-9);
+        assert!(chrf(&words("good mornin"), &_reference) > 80.0);
+        assert!(chrf(&words("xyz"), &_reference) < 10.0);
+        assert_eq!(chrf(&[], &_reference), 0.0);
+    }
+
+    #[test]
+    fn check_passes_exact_and_contracted_answers() {
+        let _check = check("Do not forget to say good morning.", "Don't forget to say good morning");
+        assert_eq!(_check.edit_distance, 0);
+        assert!(_check.passed(PASS_SCORE));
+        assert!(_check.missing.is_empty() && _check.extra.is_empty());
+    }
+
+    #[test]
+    fn check_reports_near_miss_words() {
+        let _check = check("Don't forget to say morning please", "Don't forget to say good morning");
+        assert!(!_check.passed(PASS_SCORE));
+        assert!(_check.score >= CLOSE_SCORE);
+        assert_eq!(_check.missing, vec![String::from("good")]);
+        assert_eq!(_check.extra, vec![String::from("please")]);
+        assert!(_check.grade(PASS_SCORE).explanation.starts_with("Almost."));
+        // a lower ?pass= passes the same answer
+        let _grade = _check.grade(_check.score.floor() as u8);
+        assert!(_grade.correct && _grade.explanation.starts_with("Well done."));
+    }
+
+    #[test]
+    fn check_fails_wrong_answers() {
+        let _check = check("The wolf ate grandmother", "Don't forget to say good morning");
+        assert!(_check.score < CLOSE_SCORE);
+        let _grade = _check.grade(PASS_SCORE);
+        assert!(!_grade.correct);
+        assert!(_grade.explanation.starts_with("Try again."));
+    }
+
+    #[test]
+    fn check_best_picks_the_closest_reference() {
+        let _references = vec![String::from("Say good morning"), String::from("Don't forget to say good morning")];
+        let _check = check_best("do not forget to say good morning", &_references).unwrap();
+        assert_eq!(_check.reference, _references[1]);
+        assert!(check_best("anything", &[]).is_none());
+    }
+}
@@ -0,0 +1,1306 @@
+use serde::{Deserialize, Serialize};
+use crate::common::*;
+use crate::story::*;
+use crate::judge::*;
+use crate::checker::*;
+use crate::session::*;
+use crate::settings::*;
+use crate::error::*;
+use crate::event::*;
+use crate::narration::*;
+use crate::pronunciation::*;
+
+// Game state and rules without the DOM: pages, prompts, scoring and transitions.
+// Times are passed in by the caller (Date.now() in the browser) so a playthrough can run in cargo test
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub story: Option<String>,  // title of the active story
+    pub state: PageState,
+    pub message: String,
+    pub grade: Option<Grade>,
+    pub session: Session,
+    pub settings: Settings,
+    pub judge: JudgeSettings,
+}
+
+// what the recognizer heard last on this page
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heard {
+    pub text: String,
+    pub confidence: f64,        // 0.0 - 1.0, 0.0 when the recognizer gives none
+    pub tentative: bool,        // interim result
+    pub alternatives: Vec<Alternative>,     // every alternative of a final result, best first
+}
+
+// speech recognizer, as far as its events tell
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recognizer {
+    Idle,
+    Listening,                  // started, waiting for speech
+    Processing,                 // speech over or stopped, waiting for the result and the end
+    Error(String),              // SpeechRecognitionErrorEvent.error of the last session
+}
+
+impl Recognizer {
+    // message under the mike
+
+    pub fn message(&self) -> Option<String> {
+        match self {
+            Recognizer::Idle => None,
+            Recognizer::Listening => Some(String::from(TEXT_LISTENING)),
+            Recognizer::Processing => Some(String::from(TEXT_PROCESSING)),
+            Recognizer::Error(error) => Some(match error.as_str() {
+                "not-allowed" | "service-not-allowed" => String::from(TEXT_NOT_ALLOWED),
+                "no-speech" => String::from(TEXT_NO_SPEECH),
+                "network" => String::from(TEXT_SPEECH_NETWORK),
+                "audio-capture" => String::from(TEXT_AUDIO_CAPTURE),
+                _ => format!("Speech recognition error: {}", error),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Engine {
+    stories: Vec<Story>,
+    story: Option<usize>,
+    state: PageState,
+    message: String,
+    streamed: String,           // raw JSON of a grade still being streamed
+    grade: Option<Grade>,
+    session: Session,
+    settings: Settings,
+    api_key: Option<ApiKey>,
+    judge: JudgeSettings,
+    recognizer: Recognizer,
+    speech_available: bool,     // false: keyboard only, no mike
+    heard: Option<Heard>,
+    narrating: Option<usize>,   // sentence of the chapter being read aloud
+    narration: u32,             // counts the narrations started, so a cancelled one can't go on
+    pronunciation: Option<Pronunciation>,
+    cursor: Option<usize>,      // story list row chosen with the arrow keys
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine {
+            stories: vec![Story::default()],
+            story: None,
+            state: PageState::StoryPicker,
+            message: String::from(""),
+            streamed: String::from(""),
+            grade: None,
+            session: Session::new("", 0.0),
+            settings: Settings::default(),
+            api_key: None,
+            judge: JudgeSettings::default(),
+            recognizer: Recognizer::Idle,
+            speech_available: true,
+            heard: None,
+            narrating: None,
+            narration: 0,
+            pronunciation: None,
+            cursor: None,
+        }
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine::default()
+    }
+
+    pub fn stories(&self) -> &Vec<Story> {
+        &self.stories
+    }
+    pub fn story(&self) -> &Story {
+        &self.stories[self.story.unwrap_or(0)]
+    }
+    pub fn state(&self) -> PageState {
+        self.state
+    }
+    pub fn grade(&self) -> Option<&Grade> {
+        self.grade.as_ref()
+    }
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+    pub fn judge_settings(&self) -> &JudgeSettings {
+        &self.judge
+    }
+    pub fn judge(&self) -> Option<Box<dyn TranslationJudge>> {
+        self.judge.create_judge(self.judge_key().unwrap_or(""))
+    }
+    // a key is saved, for this backend or another one
+    pub fn has_api_key(&self) -> bool {
+        self.api_key.is_some()
+    }
+    // the saved key was entered for the current backend and endpoint
+    pub fn has_judge_key(&self) -> bool {
+        self.judge_key().is_some()
+    }
+    fn judge_key(&self) -> Option<&str> {
+        self.api_key.as_ref().filter(|k| k.is_for(&self.judge) && !k.key.is_empty()).map(|k| k.key.as_str())
+    }
+    pub fn recognizer(&self) -> &Recognizer {
+        &self.recognizer
+    }
+    pub fn speech_available(&self) -> bool {
+        self.speech_available
+    }
+    pub fn heard(&self) -> Option<&Heard> {
+        self.heard.as_ref()
+    }
+
+    pub fn pronunciation(&self) -> Option<&Pronunciation> {
+        self.pronunciation.as_ref()
+    }
+
+    // pronunciation mode: the sentence to read on the chapter page
+
+    pub fn target(&self) -> Option<&String> {
+        // keyboard only: translate as usual
+        if !self.settings.pronunciation || !self.speech_available { return None; }
+        self.story().chapter(self.state.chapter()?)?.answers.first()
+    }
+
+    // sentence being read aloud, for the highlight
+
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+    pub fn narrating(&self) -> Option<Sentence> {
+        let _chapter = self.story().chapter(self.state.chapter()?)?;
+        sentences(&_chapter.text).into_iter().nth(self.narrating?)
+    }
+
+    // retry mode: the chapter is repeated until the grade passes or attempts run out
+
+    pub fn must_retry(&self) -> bool {
+        if !self.settings.retry_until_correct { return false; }
+        let _record = match self.state.chapter().and_then(|c| self.session.record(c)) {
+            Some(r) => r,
+            None => return false,
+        };
+        let _passed = _record.score().is_some_and(|s| s >= self.settings.pass_score);
+        !_passed && _record.attempts.len() < self.settings.max_attempts
+    }
+
+    // R on the feedback page: another try, within the attempts of retry mode
+
+    pub fn can_retry(&self) -> bool {
+        if !self.settings.retry_until_correct { return true; }
+        self.state.chapter().and_then(|c| self.session.record(c)).is_some_and(|r| r.attempts.len() < self.settings.max_attempts)
+    }
+
+    // text of the message box
+
+    pub fn display_message(&self) -> String {
+        match self.state {
+            PageState::AwaitingResponse { .. } if self.message.is_empty() => String::from(TEXT_WAITING),
+            _ => self.message.clone(),
+        }
+    }
+
+    pub fn set_stories(&mut self, stories: Vec<Story>) {
+        if stories.is_empty() { return; }
+        self.stories = stories;
+        self.story = None;
+        self.state = PageState::StoryPicker;
+    }
+    pub fn select_story(&mut self, index: usize, now: f64) -> bool {
+        if index >= self.stories.len() { return false; }
+        if !self.transition(PageAction::SelectStory, now) { return false; }
+        self.story = Some(index);
+        self.session = Session::new(&self.stories[index].title, now);
+        // skip API key entry once the key is known, or when the backend needs none
+        if self.has_judge_key() || !self.judge.backend.needs_key() {
+            self.transition(PageAction::SubmitKey, now);
+        }
+        true
+    }
+    pub fn set_speech_available(&mut self, available: bool) {
+        self.speech_available = available;
+        self.recognizer = Recognizer::Idle;
+    }
+
+    // a key entered for the current backend and endpoint
+    pub fn set_api_key(&mut self, api_key: String) {
+        self.api_key = Some(ApiKey::new(&api_key, &self.judge)).filter(|k| !k.key.is_empty());
+    }
+    // a saved key, still bound to where it was entered
+    pub fn restore_api_key(&mut self, api_key: ApiKey) {
+        self.api_key = Some(api_key);
+    }
+    pub fn forget_api_key(&mut self) {
+        self.api_key = None;
+    }
+    pub fn set_judge_settings(&mut self, judge: JudgeSettings) {
+        self.judge = judge;
+    }
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            story: self.story.map(|i| self.stories[i].title.clone()),
+            state: self.state,
+            message: self.message.clone(),
+            grade: self.grade.clone(),
+            session: self.session.clone(),
+            settings: self.settings.clone(),
+            // kept with its endpoint: the saved key is bound to the one it was entered for
+            judge: self.judge.clone(),
+        }
+    }
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.settings = snapshot.settings;
+        self.judge = snapshot.judge;
+        let _index = snapshot.story.and_then(|title| self.stories.iter().position(|s| s.title == title));
+        let _chapters = _index.map(|i| self.stories[i].chapters.len()).unwrap_or(0);
+        let _state = match snapshot.state {
+            // the request was lost with the page: ask again
+            PageState::AwaitingResponse { chapter } => PageState::ChapterPrompt { chapter },
+            _state => _state,
+        };
+        let _valid = match (_index, _state.chapter()) {
+            (None, _) => false,
+            (Some(_), Some(_chapter)) => _chapter >= 1 && _chapter <= _chapters,
+            (Some(_), None) => _state != PageState::StoryPicker,
+        };
+        if !_valid {
+            self.story = None;
+            self.state = PageState::StoryPicker;
+            return;
+        }
+        self.story = _index;
+        self.state = _state;
+        self.message = snapshot.message;
+        self.grade = snapshot.grade;
+        self.session = snapshot.session;
+    }
+
+    // the only place the page changes
+
+    pub fn transition(&mut self, action: PageAction, now: f64) -> bool {
+        let _chapters = self.story().chapters.len();
+        let _next = match self.state.next(action, _chapters) {
+            Some(_next) => _next,
+            None => return false,
+        };
+        // session record
+        if let (PageAction::Continue, Some(_chapter)) = (action, self.state.chapter()) {
+            self.session.finish_chapter(_chapter, now);
+        }
+        match _next {
+            PageState::ChapterPrompt { chapter } => self.session.start_chapter(chapter, now),
+            PageState::Finished => self.session.finish(now),
+            PageState::StoryPicker => self.story = None,
+            _ => {}
+        }
+        self.state = _next;
+        self.heard = None;
+        self.pronunciation = None;
+        self.cursor = None;
+        true
+    }
+
+    pub fn submit_answer(&mut self, answer: &str, now: f64) -> bool {
+        let _chapter = self.state.chapter().unwrap_or(0);
+        if !self.transition(PageAction::SubmitAnswer, now) { return false; }
+        self.session.record_answer(_chapter, answer);
+        self.message = String::from("");
+        self.streamed = String::from("");
+        true
+    }
+
+    // judge answer; false when it arrives after the page has moved on
+
+    pub fn receive_response(&mut self, response: Result<Grade>, now: f64) -> bool {
+        if !self.transition(PageAction::ReceiveResponse, now) { return false; }
+        let _chapter = self.state.chapter().unwrap_or(0);
+        // the verdict follows ?pass=, whatever the backend thought of it
+        let _pass_score = self.settings.pass_score;
+        let response = response.map(|mut _grade| {
+            _grade.correct = _grade.score >= _pass_score;
+            _grade
+        });
+        self.session.record_grade(_chapter, response.as_ref().ok().cloned());
+        self.grade = response.as_ref().ok().cloned();
+        let _reveal = !self.must_retry();
+        let mut _message = match &response {
+            Ok(_grade) => _grade.render(_reveal),
+            Err(e) => e.user_message(),
+        };
+
+        // out of attempts: show the answer and continue
+        if self.settings.retry_until_correct && !self.must_retry() {
+            let _passed = self.grade.as_ref().is_some_and(|g| g.score >= self.settings.pass_score);
+            let _answer = self.story().chapter(_chapter).and_then(|c| c.answers.first().cloned());
+            if let (false, Some(_answer)) = (_passed, _answer) {
+                _message = format!("{}\n\nAnswer: {}", _message, _answer);
+            }
+        }
+        self.message = _message;
+        true
+    }
+
+    // streamed response: the grade is JSON, only its explanation is typed out while waiting
+
+    pub fn receive_chunk(&mut self, text: &str) {
+        if matches!(self.state, PageState::AwaitingResponse { .. }) {
+            self.streamed.push_str(text);
+            if let Some(_explanation) = Grade::partial_explanation(&self.streamed) {
+                self.message = _explanation;
+            }
+        }
+    }
+
+    // every input goes through here; the effects are left to the browser
+
+    pub fn handle(&mut self, event: &GameEvent, now: f64) -> Vec<Effect> {
+        let _state = self.state;
+        let mut _effects = self.react(event, now);
+        if self.state != _state {
+            _effects.extend(self.stop_listening());
+            _effects.extend(self.narrate_page());
+            // left without an answer: the request still in flight is not wanted any more
+            if matches!(_state, PageState::AwaitingResponse { .. }) && !matches!(event, GameEvent::Response { .. }) {
+                _effects.push(Effect::CancelJudge);
+            }
+        }
+        _effects
+    }
+
+    fn react(&mut self, event: &GameEvent, now: f64) -> Vec<Effect> {
+        match event {
+            GameEvent::Frame => Vec::new(),
+            GameEvent::Click { x, y } => {
+                if *x >= READ_ALOUD_LEFT && *y < READ_ALOUD_BOTTOM && matches!(self.state, PageState::ChapterPrompt { .. } | PageState::Feedback { .. }) {
+                    return self.narrate();
+                }
+                let _row = ((y - ALTERNATIVES_TOP) / ALTERNATIVES_ROW).round();
+                if _row >= 0.0 && let Some(_text) = self.choose_alternative(_row as usize) {
+                    return vec![Effect::SetInput(_text)];
+                }
+                if matches!(self.state, PageState::ChapterPrompt { .. }) {
+                    return self.toggle_mike();
+                }
+                self.settings_effects(|engine| engine.click(*y, now))
+            },
+            GameEvent::Enter { text } => self.enter(text, now),
+            GameEvent::Command { command } => self.command(*command, now),
+            GameEvent::Transcript { text, confidence, alternatives } => {
+                // a single utterance ends with its final result
+                if self.recognizer == Recognizer::Listening && !self.settings.speech.continuous {
+                    self.recognizer = Recognizer::Processing;
+                }
+                // a result arriving after the page has changed has no answer box to go to
+                if !matches!(self.state, PageState::ChapterPrompt { .. }) { return Vec::new(); }
+                let mut _alternatives = vec![Alternative { text: text.clone(), confidence: *confidence }];
+                _alternatives.extend(alternatives.iter().cloned());
+                self.heard = Some(Heard { text: text.clone(), confidence: *confidence, tentative: false, alternatives: _alternatives });
+                if self.pronounce(text, *confidence, now) {
+                    return vec![Effect::ClearInput];
+                }
+                vec![Effect::SetInput(text.clone())]
+            },
+            GameEvent::InterimTranscript { text, confidence } => {
+                if !matches!(self.recognizer, Recognizer::Listening | Recognizer::Processing) { return Vec::new(); }
+                if !matches!(self.state, PageState::ChapterPrompt { .. }) { return Vec::new(); }
+                self.heard = Some(Heard { text: text.clone(), confidence: *confidence, tentative: true, alternatives: Vec::new() });
+                vec![Effect::SetTentativeInput(text.clone())]
+            },
+            GameEvent::SpeechProcessing => {
+                if self.recognizer == Recognizer::Listening {
+                    self.recognizer = Recognizer::Processing;
+                }
+                Vec::new()
+            },
+            GameEvent::SpeechError { error } => {
+                // aborted: stopped by the page, the end follows
+                if error != "aborted" {
+                    self.recognizer = Recognizer::Error(error.clone());
+                }
+                Vec::new()
+            },
+            GameEvent::SpeechEnd => {
+                if matches!(self.recognizer, Recognizer::Listening | Recognizer::Processing) {
+                    self.recognizer = Recognizer::Idle;
+                }
+                Vec::new()
+            },
+            GameEvent::ResponseChunk { text } => {
+                self.receive_chunk(text);
+                Vec::new()
+            },
+            GameEvent::Response { result } => {
+                self.receive_response(result.clone(), now);
+                Vec::new()
+            },
+            GameEvent::NarrationEnd { narration, sentence } => {
+                if *narration != self.narration || self.narrating != Some(*sentence) { return Vec::new(); }
+                self.narrating = None;
+                self.speak_sentence(sentence + 1)
+            },
+        }
+    }
+
+    // story list rows change the API key and the speech settings, which the browser keeps
+
+    fn settings_effects(&mut self, action: impl FnOnce(&mut Engine) -> bool) -> Vec<Effect> {
+        let mut _effects = Vec::new();
+        let _had_key = self.has_api_key();
+        let _speech = self.settings.speech.clone();
+        if action(self) {
+            _effects.push(Effect::ClearInput);
+        }
+        if _had_key && !self.has_api_key() {
+            _effects.push(Effect::ForgetApiKey);
+        }
+        if self.settings.speech != _speech {
+            _effects.push(Effect::ConfigureSpeech(self.settings.speech.clone()));
+        }
+        _effects
+    }
+
+    // keys other than Enter, so the game is played without a mouse
+
+    fn command(&mut self, command: Command, now: f64) -> Vec<Effect> {
+        match command {
+            Command::Continue => match (self.state, self.cursor) {
+                (PageState::StoryPicker, Some(_row)) => self.settings_effects(|engine| { engine.pick(_row, now); false }),
+                (PageState::Feedback { .. } | PageState::Finished, _) => self.settings_effects(|engine| engine.click(0.0, now)),
+                _ => Vec::new(),
+            },
+            Command::Back => {
+                // first what is running on the page
+                if self.recognizer == Recognizer::Listening {
+                    return self.toggle_mike();
+                }
+                if self.narrating.is_some() {
+                    return self.narrate();
+                }
+                self.message = String::from("");
+                if self.transition(PageAction::Back, now) { vec![Effect::ClearInput] } else { Vec::new() }
+            },
+            Command::ToggleMike => self.toggle_mike(),
+            Command::ReadAloud => self.narrate(),
+            Command::Retry => {
+                if !matches!(self.state, PageState::Feedback { .. }) || !self.can_retry() { return Vec::new(); }
+                self.message = String::from("");
+                self.transition(PageAction::Retry, now);
+                vec![Effect::ClearInput]
+            },
+            Command::Up | Command::Down => {
+                if self.state == PageState::StoryPicker {
+                    self.move_cursor(command == Command::Down);
+                    return Vec::new();
+                }
+                // the speech alternatives, when there are any
+                let _next = self.heard.as_ref().filter(|h| !h.tentative && h.alternatives.len() > 1).map(|h| {
+                    let _count = h.alternatives.len();
+                    let _current = h.alternatives.iter().position(|a| a.text == h.text).unwrap_or(0);
+                    if command == Command::Down { (_current + 1) % _count } else { (_current + _count - 1) % _count }
+                });
+                match _next.and_then(|i| self.choose_alternative(i)) {
+                    Some(_text) => vec![Effect::SetInput(_text)],
+                    None => Vec::new(),
+                }
+            },
+        }
+    }
+
+    // story list rows that do something, top to bottom; wraps around
+
+    fn move_cursor(&mut self, down: bool) {
+        let _stories = self.stories.len();
+        let mut _rows: Vec<usize> = (0.._stories).collect();
+        if self.has_api_key() {
+            _rows.push(_stories + 1);
+        }
+        _rows.push(_stories + 2);
+        let _count = _rows.len();
+        let _index = match self.cursor.and_then(|c| _rows.iter().position(|r| *r == c)) {
+            Some(i) if down => (i + 1) % _count,
+            Some(i) => (i + _count - 1) % _count,
+            None if down => 0,
+            None => _count - 1,
+        };
+        self.cursor = Some(_rows[_index]);
+    }
+
+    // push to talk on the chapter page: start, or stop and wait for the result.
+    // start() is never called on a running recognizer
+
+    pub fn toggle_mike(&mut self) -> Vec<Effect> {
+        if !self.speech_available || !matches!(self.state, PageState::ChapterPrompt { .. }) { return Vec::new(); }
+        match self.recognizer {
+            Recognizer::Idle | Recognizer::Error(_) => {
+                self.recognizer = Recognizer::Listening;
+                self.heard = None;
+                let mut _effects = Vec::new();
+                // the mike must not hear the narration
+                if self.narrating.take().is_some() {
+                    _effects.push(Effect::CancelSpeech);
+                }
+                _effects.push(Effect::StartRecognition);
+                _effects
+            },
+            Recognizer::Listening => {
+                self.recognizer = Recognizer::Processing;
+                vec![Effect::StopRecognition]
+            },
+            Recognizer::Processing => Vec::new(),
+        }
+    }
+
+    // a new page: the recognizer is stopped, its last error forgotten
+
+    fn stop_listening(&mut self) -> Vec<Effect> {
+        match self.recognizer {
+            Recognizer::Listening => {
+                self.recognizer = Recognizer::Processing;
+                vec![Effect::StopRecognition]
+            },
+            Recognizer::Error(_) => {
+                self.recognizer = Recognizer::Idle;
+                Vec::new()
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    // read aloud button: the chapter (again to stop), or the corrected answer
+
+    fn narrate(&mut self) -> Vec<Effect> {
+        match self.state {
+            PageState::ChapterPrompt { .. } => {
+                if self.narrating.take().is_some() {
+                    return vec![Effect::CancelSpeech];
+                }
+                let mut _effects = vec![Effect::CancelSpeech];
+                self.narration = self.narration.wrapping_add(1);
+                _effects.extend(self.speak_sentence(0));
+                _effects
+            },
+            PageState::Feedback { .. } => match self.grade.as_ref() {
+                Some(_grade) if !_grade.corrected_sentence.is_empty() && !self.must_retry() => {
+                    vec![Effect::CancelSpeech, Effect::Speak { text: _grade.corrected_sentence.clone(), narration: self.narration, sentence: None }]
+                },
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    fn speak_sentence(&mut self, index: usize) -> Vec<Effect> {
+        match self.story().chapter(self.state.chapter().unwrap_or(0)).and_then(|c| sentences(&c.text).into_iter().nth(index)) {
+            Some(_sentence) => {
+                self.narrating = Some(index);
+                vec![Effect::Speak { text: _sentence.text, narration: self.narration, sentence: Some(index) }]
+            },
+            None => Vec::new(),
+        }
+    }
+
+    // a new page stops the narration of the last one, and is read when narration is automatic
+
+    fn narrate_page(&mut self) -> Vec<Effect> {
+        let mut _effects = Vec::new();
+        if self.narrating.take().is_some() {
+            _effects.push(Effect::CancelSpeech);
+        }
+        if self.settings.narration.auto && matches!(self.state, PageState::ChapterPrompt { .. } | PageState::Feedback { .. }) {
+            _effects.extend(self.narrate());
+            _effects.dedup();
+        }
+        _effects
+    }
+
+    // Enter key: story number, API key or answer, depending on the page;
+    // otherwise the same as Space
+
+    fn enter(&mut self, text: &str, now: f64) -> Vec<Effect> {
+        match self.state {
+            PageState::StoryPicker => {
+                if let Ok(_number) = text.trim().parse::<usize>() && _number > 0 {
+                    self.select_story(_number - 1, now);
+                } else if text.trim().is_empty() {
+                    let mut _effects = self.command(Command::Continue, now);
+                    _effects.push(Effect::ClearInput);
+                    return _effects;
+                }
+                vec![Effect::ClearInput]
+            },
+            PageState::ApiKeyEntry => {
+                if text.is_empty() { return vec![Effect::ClearInput]; }
+                self.set_api_key(text.to_string());
+                self.transition(PageAction::SubmitKey, now);
+                vec![Effect::SaveApiKey(ApiKey::new(text, &self.judge)), Effect::ClearInput]
+            },
+            PageState::ChapterPrompt { .. } => {
+                // nothing typed, e.g. Enter held down after the feedback page
+                if text.trim().is_empty() { return Vec::new(); }
+                // pronunciation is scored from what was said, not typed
+                if self.target().is_some() { return Vec::new(); }
+                // answered locally
+                if let Some(_grade) = self.check_answer(text) {
+                    self.submit_answer(text, now);
+                    self.receive_response(Ok(_grade), now);
+                    return Vec::new();
+                }
+                if self.judge().is_none() { return Vec::new(); }
+                let _prompt = self.create_prompt(text);
+                self.submit_answer(text, now);
+                vec![Effect::Judge { prompt: _prompt }]
+            },
+            PageState::Feedback { .. } | PageState::Finished => self.command(Command::Continue, now),
+            PageState::AwaitingResponse { .. } => Vec::new(),
+        }
+    }
+
+    // click on the canvas; true when the answer box should be emptied
+
+    pub fn click(&mut self, y: f64, now: f64) -> bool {
+        match self.state {
+            PageState::StoryPicker => {
+                let _row = ((y - PICKER_TOP) / PICKER_ROW).round();
+                if _row >= 0.0 {
+                    self.pick(_row as usize, now);
+                }
+                false
+            },
+            PageState::ApiKeyEntry | PageState::AwaitingResponse { .. } | PageState::ChapterPrompt { .. } => false,
+            PageState::Finished => {
+                self.message = String::from("");
+                self.transition(PageAction::Continue, now);
+                true
+            },
+            PageState::Feedback { .. } => {
+                self.message = String::from("");
+                let _action = if self.must_retry() { PageAction::Retry } else { PageAction::Continue };
+                self.transition(_action, now);
+                true
+            }
+        }
+    }
+
+    // story list row, clicked or chosen with the keyboard
+
+    fn pick(&mut self, row: usize, now: f64) {
+        let _stories = self.stories.len();
+        if row < _stories {
+            self.select_story(row, now);
+        } else if row == _stories + 1 && self.has_api_key() {
+            self.forget_api_key();
+        } else if row == _stories + 2 {
+            self.settings.speech.next_language();
+        }
+    }
+
+    // pronunciation mode: what was said, graded against the target sentence
+
+    fn pronounce(&mut self, text: &str, confidence: f64, now: f64) -> bool {
+        let _target = match self.target() {
+            Some(target) if matches!(self.state, PageState::ChapterPrompt { .. }) && !text.trim().is_empty() => target.clone(),
+            _ => return false,
+        };
+        let _pronunciation = align(&_target, text, confidence);
+        self.submit_answer(text, now);
+        self.receive_response(Ok(_pronunciation.grade(self.settings.pass_score)), now);
+        self.pronunciation = Some(_pronunciation);
+        true
+    }
+
+    // another alternative of the last transcript as the answer, on the chapter page
+
+    pub fn choose_alternative(&mut self, index: usize) -> Option<String> {
+        if !matches!(self.state, PageState::ChapterPrompt { .. }) { return None; }
+        let _heard = self.heard.as_mut()?;
+        if _heard.alternatives.len() < 2 { return None; }
+        let _alternative = _heard.alternatives.get(index)?.clone();
+        _heard.text = _alternative.text.clone();
+        _heard.confidence = _alternative.confidence;
+        Some(_alternative.text)
+    }
+
+    // Gemini Prompt
+
+    pub fn create_prompt(&self, text: &str) -> String {
+        let _chapter = self.state.chapter().unwrap_or(0);
+        match self.story().chapter(_chapter) {
+            Some(_c) => {
+                format!("「{}」の英訳として「{}」は正しいですか？\n次の JSON で答えてください。\ncorrect: 正しければ true\nscore: 0 から 100 の点数\ncorrected_sentence: 正しい英文\nexplanation: 日本語の解説", _c.blank, text)
+            },
+            None => {
+                String::from("")
+            }
+        }
+    }
+
+    // Offline check: feedback when the backend needs no HTTP
+
+    pub fn check_answer(&self, text: &str) -> Option<Grade> {
+        if self.judge.backend != Backend::Offline { return None; }
+        let _chapter = self.state.chapter().unwrap_or(0);
+        let _answers = self.story().chapter(_chapter).map(|c| c.answers.clone()).unwrap_or_default();
+        match check_best(text, &_answers) {
+            Some(_check) => Some(_check.grade(self.settings.pass_score)),
+            // nothing to compare with: passed, so retry mode moves on
+            None => Some(Grade {
+                correct: true,
+                score: 100,
+                corrected_sentence: String::from(""),
+                explanation: String::from("No reference answer for this chapter"),
+                missing: Vec::new(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: f64 = 60_000.0;
+
+    fn offline() -> Engine {
+        let mut _engine = Engine::new();
+        _engine.set_judge_settings(JudgeSettings::new(Backend::Offline));
+        _engine
+    }
+
+    #[test]
+    fn offline_playthrough_scores_every_chapter() {
+        let mut _engine = offline();
+        let _chapters = _engine.story().chapters.len();
+        assert!(_engine.select_story(0, 0.0));
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+
+        let mut _now = 0.0;
+        for _chapter in 1..=_chapters {
+            let _answer = _engine.story().chapter(_chapter).unwrap().answers[0].clone();
+            let _grade = _engine.check_answer(&_answer).unwrap();
+            assert!(_grade.correct);
+            assert!(_engine.submit_answer(&_answer, _now));
+            assert_eq!(_engine.display_message(), TEXT_WAITING);
+            assert!(_engine.receive_response(Ok(_grade), _now));
+            assert_eq!(_engine.state(), PageState::Feedback { chapter: _chapter });
+            _now += MINUTE;
+            assert!(_engine.click(0.0, _now));
+        }
+
+        assert_eq!(_engine.state(), PageState::Finished);
+        let _session = _engine.session();
+        assert_eq!(_session.chapters.len(), _chapters);
+        assert_eq!(_session.total_score(), _session.max_score());
+        assert_eq!(_session.elapsed(), MINUTE * _chapters as f64);
+        assert!(_session.chapters.iter().all(|r| r.retries() == 0 && r.elapsed() == MINUTE));
+
+        _engine.click(0.0, _now);
+        assert_eq!(_engine.state(), PageState::StoryPicker);
+    }
+
+    #[test]
+    fn api_key_is_asked_once() {
+        let mut _engine = Engine::new();
+        assert!(_engine.select_story(0, 0.0));
+        assert_eq!(_engine.state(), PageState::ApiKeyEntry);
+        assert!(_engine.judge().is_some());
+        _engine.set_api_key(String::from("key"));
+        assert!(_engine.transition(PageAction::SubmitKey, 0.0));
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+
+        let mut _engine = Engine::new();
+        _engine.set_api_key(String::from("key"));
+        _engine.select_story(0, 0.0);
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+    }
+
+    #[test]
+    fn picker_click_selects_row_and_forgets_key() {
+        let mut _engine = offline();
+        _engine.set_api_key(String::from("key"));
+        let _forget = PICKER_TOP + PICKER_ROW * (_engine.stories().len() + 1) as f64;
+        _engine.click(_forget, 0.0);
+        assert!(!_engine.has_api_key());
+        assert_eq!(_engine.state(), PageState::StoryPicker);
+        _engine.click(PICKER_TOP, 0.0);
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+    }
+
+    #[test]
+    fn language_row_cycles_and_is_kept_in_the_snapshot() {
+        let mut _engine = offline();
+        let _language = PICKER_TOP + PICKER_ROW * (_engine.stories().len() + 2) as f64;
+        let _effects = _engine.handle(&GameEvent::Click { x: 0.0, y: _language }, 0.0);
+        assert_eq!(_engine.settings().speech.lang, LANGUAGES[1].0);
+        assert_eq!(_effects, vec![Effect::ConfigureSpeech(_engine.settings().speech.clone())]);
+        assert_eq!(_engine.snapshot().settings.speech.lang, LANGUAGES[1].0);
+
+        for _ in 1..LANGUAGES.len() {
+            _engine.click(_language, 0.0);
+        }
+        assert_eq!(_engine.settings().speech.lang, LANGUAGES[0].0);
+        assert_eq!(_engine.state(), PageState::StoryPicker);
+    }
+
+    #[test]
+    fn interim_transcript_is_tentative_until_final() {
+        let mut _engine = offline();
+        _engine.select_story(0, 0.0);
+        let _interim = GameEvent::InterimTranscript { text: String::from("Do not"), confidence: 0.0 };
+        assert!(_engine.handle(&_interim, 0.0).is_empty());
+        assert!(_engine.heard().is_none());
+
+        _engine.handle(&GameEvent::Click { x: 0.0, y: 0.0 }, 0.0);
+        assert_eq!(_engine.handle(&_interim, 0.0), vec![Effect::SetTentativeInput(String::from("Do not"))]);
+        assert!(_engine.heard().unwrap().tentative);
+
+        let _final = GameEvent::Transcript { text: String::from("Do not forget"), confidence: 0.9, alternatives: Vec::new() };
+        assert_eq!(_engine.handle(&_final, 0.0), vec![Effect::SetInput(String::from("Do not forget"))]);
+        assert_eq!(_engine.heard().map(|h| (h.text.as_str(), h.tentative)), Some(("Do not forget", false)));
+        assert_ne!(_engine.recognizer(), &Recognizer::Listening);
+
+        // results still coming after the answer has been sent are dropped
+        _engine.handle(&GameEvent::Click { x: 0.0, y: 300.0 }, 0.0);
+        _engine.handle(&GameEvent::Enter { text: String::from("Do not forget") }, 0.0);
+        assert_ne!(_engine.state(), PageState::ChapterPrompt { chapter: 0 });
+        assert_eq!(_engine.recognizer(), &Recognizer::Processing);
+        assert!(_engine.handle(&_interim, 0.0).is_empty());
+        assert!(_engine.handle(&_final, 0.0).is_empty());
+    }
+
+    #[test]
+    fn click_on_an_alternative_replaces_the_answer() {
+        let mut _engine = offline();
+        _engine.select_story(0, 0.0);
+        _engine.handle(&GameEvent::Click { x: 0.0, y: 0.0 }, 0.0);
+        let _alternatives = vec![Alternative { text: String::from("Do not for get"), confidence: 0.4 }];
+        _engine.handle(&GameEvent::Transcript { text: String::from("Do not forge"), confidence: 0.8, alternatives: _alternatives }, 0.0);
+        assert_eq!(_engine.heard().unwrap().alternatives.len(), 2);
+        _engine.handle(&GameEvent::SpeechEnd, 0.0);
+
+        let _second = ALTERNATIVES_TOP + ALTERNATIVES_ROW;
+        assert_eq!(_engine.handle(&GameEvent::Click { x: 0.0, y: _second }, 0.0), vec![Effect::SetInput(String::from("Do not for get"))]);
+        assert_eq!(_engine.heard().unwrap().confidence, 0.4);
+        assert_ne!(_engine.recognizer(), &Recognizer::Listening);
+
+        // anywhere else listens again
+        assert_eq!(_engine.handle(&GameEvent::Click { x: 0.0, y: 300.0 }, 0.0), vec![Effect::StartRecognition]);
+        assert!(_engine.choose_alternative(0).is_none());
+    }
+
+    #[test]
+    fn chapter_is_read_aloud_sentence_by_sentence() {
+        let mut _engine = offline();
+        _engine.select_story(0, 0.0);
+        let _sentences = sentences(&_engine.story().chapters[0].text);
+
+        let _read = GameEvent::Click { x: READ_ALOUD_LEFT, y: 0.0 };
+        assert_eq!(_engine.handle(&_read, 0.0), vec![Effect::CancelSpeech, Effect::Speak { text: _sentences[0].text.clone(), narration: 1, sentence: Some(0) }]);
+        assert_eq!(_engine.narrating(), Some(_sentences[0].clone()));
+
+        // a stale end is ignored, the current one moves on
+        assert!(_engine.handle(&GameEvent::NarrationEnd { narration: 1, sentence: 5 }, 0.0).is_empty());
+        assert_eq!(_engine.handle(&GameEvent::NarrationEnd { narration: 1, sentence: 0 }, 0.0), vec![Effect::Speak { text: _sentences[1].text.clone(), narration: 1, sentence: Some(1) }]);
+
+        // stopped and started again: the end of the cancelled utterance doesn't skip a sentence
+        assert_eq!(_engine.handle(&_read, 0.0), vec![Effect::CancelSpeech]);
+        _engine.handle(&GameEvent::Command { command: Command::from_key("n").unwrap() }, 0.0);
+        assert!(_engine.handle(&GameEvent::NarrationEnd { narration: 1, sentence: 0 }, 0.0).is_empty());
+        assert_eq!(_engine.narrating(), Some(_sentences[0].clone()));
+        assert_eq!(_engine.handle(&GameEvent::NarrationEnd { narration: 2, sentence: 0 }, 0.0), vec![Effect::Speak { text: _sentences[1].text.clone(), narration: 2, sentence: Some(1) }]);
+
+        // listening stops the narration
+        assert_eq!(_engine.handle(&GameEvent::Click { x: 0.0, y: 300.0 }, 0.0), vec![Effect::CancelSpeech, Effect::StartRecognition]);
+        assert!(_engine.narrating().is_none());
+    }
+
+    #[test]
+    fn automatic_narration_reads_pages_and_corrected_answer() {
+        let mut _engine = offline();
+        let mut _settings = _engine.settings().clone();
+        _settings.narration.auto = true;
+        _engine.set_settings(_settings);
+
+        let _effects = _engine.handle(&GameEvent::Enter { text: String::from("1") }, 0.0);
+        assert!(_effects.iter().any(|e| matches!(e, Effect::Speak { sentence: Some(0), .. })));
+
+        _engine.set_judge_settings(JudgeSettings::default());
+        _engine.set_api_key(String::from("key"));
+        _engine.handle(&GameEvent::Enter { text: String::from("answer") }, 0.0);
+        let _effects = _engine.handle(&GameEvent::Response { result: Ok(Grade::sample(90)) }, 0.0);
+        assert_eq!(_effects, vec![Effect::CancelSpeech, Effect::Speak { text: Grade::sample(90).corrected_sentence, narration: 1, sentence: None }]);
+
+        // nothing to read on the feedback page: the chapter still stops
+        let _settings = _engine.settings().clone();
+        let mut _engine = offline();
+        let mut _story = Story::default();
+        _story.chapters[0].answers.clear();
+        _engine.set_stories(vec![_story]);
+        _engine.set_settings(_settings);
+        _engine.select_story(0, 0.0);
+        _engine.handle(&GameEvent::Frame, 0.0);
+        _engine.narrate_page();
+        assert!(_engine.narrating().is_some());
+        let _effects = _engine.handle(&GameEvent::Enter { text: String::from("anything") }, 0.0);
+        assert_eq!(_effects, vec![Effect::CancelSpeech]);
+    }
+
+    #[test]
+    fn pronunciation_mode_grades_the_spoken_sentence() {
+        let mut _engine = offline();
+        let mut _settings = _engine.settings().clone();
+        _settings.pronunciation = true;
+        _engine.set_settings(_settings);
+        _engine.select_story(0, 0.0);
+        let _target = _engine.target().unwrap().clone();
+        // typing the sentence is not reading it
+        assert!(_engine.handle(&GameEvent::Enter { text: _target.clone() }, 0.0).is_empty());
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+        _engine.handle(&GameEvent::Click { x: 0.0, y: 0.0 }, 0.0);
+        let _effects = _engine.handle(&GameEvent::Transcript { text: _target.clone(), confidence: 0.95, alternatives: Vec::new() }, 0.0);
+        assert_eq!(_effects, vec![Effect::ClearInput]);
+        assert_eq!(_engine.state(), PageState::Feedback { chapter: 1 });
+        assert_eq!(_engine.grade().unwrap().score, 100);
+        assert_eq!(_engine.session().record(1).unwrap().score(), Some(100));
+        assert!(_engine.pronunciation().is_some());
+        _engine.click(300.0, 300.0);
+        assert!(_engine.pronunciation().is_none());
+
+        // keyboard only: the chapter is translated as usual
+        _engine.set_speech_available(false);
+        assert!(_engine.target().is_none());
+    }
+
+    #[test]
+    fn push_to_talk_follows_the_recognizer() {
+        let mut _engine = offline();
+        _engine.select_story(0, 0.0);
+        let _click = GameEvent::Click { x: 0.0, y: 300.0 };
+        assert_eq!(_engine.handle(&_click, 0.0), vec![Effect::StartRecognition]);
+        assert_eq!(_engine.recognizer(), &Recognizer::Listening);
+
+        // second click stops; no start() until the recognizer has ended
+        assert_eq!(_engine.handle(&_click, 0.0), vec![Effect::StopRecognition]);
+        assert_eq!(_engine.recognizer(), &Recognizer::Processing);
+        assert!(_engine.handle(&_click, 0.0).is_empty());
+        _engine.handle(&GameEvent::SpeechEnd, 0.0);
+        assert_eq!(_engine.recognizer(), &Recognizer::Idle);
+
+        // start() failed: the error alone, without an end, makes the mike ready again
+        _engine.handle(&_click, 0.0);
+        _engine.handle(&GameEvent::SpeechError { error: String::from("not-allowed") }, 0.0);
+        assert_eq!(_engine.recognizer().message(), Some(String::from(TEXT_NOT_ALLOWED)));
+        assert_eq!(_engine.handle(&_click, 0.0), vec![Effect::StartRecognition]);
+        _engine.handle(&GameEvent::SpeechEnd, 0.0);
+
+        // errors stay on the page until the next try
+        _engine.handle(&_click, 0.0);
+        _engine.handle(&GameEvent::SpeechError { error: String::from("no-speech") }, 0.0);
+        _engine.handle(&GameEvent::SpeechEnd, 0.0);
+        assert_eq!(_engine.recognizer().message(), Some(String::from(TEXT_NO_SPEECH)));
+        assert_eq!(_engine.handle(&_click, 0.0), vec![Effect::StartRecognition]);
+
+        // leaving the page stops listening
+        _engine.handle(&GameEvent::SpeechProcessing, 0.0);
+        assert_eq!(_engine.recognizer(), &Recognizer::Processing);
+        _engine.handle(&GameEvent::SpeechEnd, 0.0);
+        _engine.handle(&_click, 0.0);
+        let _effects = _engine.handle(&GameEvent::Enter { text: _engine.story().chapters[0].answers[0].clone() }, 0.0);
+        assert_eq!(_effects, vec![Effect::StopRecognition]);
+        assert_eq!(_engine.state(), PageState::Feedback { chapter: 1 });
+    }
+
+    #[test]
+    fn keyboard_only_without_speech_recognition() {
+        let mut _engine = offline();
+        _engine.set_speech_available(false);
+        _engine.select_story(0, 0.0);
+        assert!(_engine.handle(&GameEvent::Click { x: 0.0, y: 300.0 }, 0.0).is_empty());
+        assert_eq!(_engine.recognizer(), &Recognizer::Idle);
+        _engine.handle(&GameEvent::Enter { text: _engine.story().chapters[0].answers[0].clone() }, 0.0);
+        assert_eq!(_engine.state(), PageState::Feedback { chapter: 1 });
+    }
+
+    #[test]
+    fn played_with_the_keyboard_only() {
+        let mut _engine = offline();
+        let _key = |command| GameEvent::Command { command };
+        let _language = _engine.stories().len() + 2;
+
+        // story list: arrows wrap around, Enter or Space uses the row
+        _engine.handle(&_key(Command::Up), 0.0);
+        assert_eq!(_engine.cursor(), Some(_language));
+        let _effects = _engine.handle(&GameEvent::Enter { text: String::new() }, 0.0);
+        assert_eq!(_effects, vec![Effect::ConfigureSpeech(_engine.settings().speech.clone()), Effect::ClearInput]);
+        _engine.handle(&_key(Command::Down), 0.0);
+        assert_eq!(_engine.cursor(), Some(0));
+        _engine.handle(&_key(Command::Continue), 0.0);
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+
+        // M talks, Esc stops listening first, then goes back
+        assert_eq!(_engine.handle(&_key(Command::ToggleMike), 0.0), vec![Effect::StartRecognition]);
+        assert_eq!(_engine.handle(&_key(Command::Back), 0.0), vec![Effect::StopRecognition]);
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+        _engine.handle(&GameEvent::SpeechEnd, 0.0);
+
+        // R repeats the chapter, Enter continues
+        _engine.handle(&GameEvent::Enter { text: String::from("wrong") }, 0.0);
+        assert_eq!(_engine.state(), PageState::Feedback { chapter: 1 });
+        assert_eq!(_engine.handle(&_key(Command::Retry), 0.0), vec![Effect::ClearInput]);
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+        _engine.handle(&GameEvent::Enter { text: _engine.story().chapters[0].answers[0].clone() }, 0.0);
+        _engine.handle(&GameEvent::Enter { text: String::new() }, 0.0);
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 2 });
+        assert_eq!(_engine.session().chapters[0].retries(), 1);
+
+        _engine.handle(&_key(Command::Back), 0.0);
+        assert_eq!(_engine.state(), PageState::StoryPicker);
+        assert!(_engine.handle(&_key(Command::Back), 0.0).is_empty());
+    }
+
+    #[test]
+    fn esc_while_waiting_cancels_the_request() {
+        let mut _engine = Engine::new();
+        _engine.set_judge_settings(JudgeSettings::new(Backend::Ollama));
+        _engine.select_story(0, 0.0);
+        _engine.handle(&GameEvent::Enter { text: String::from("Do not forget") }, 0.0);
+        assert_eq!(_engine.state(), PageState::AwaitingResponse { chapter: 1 });
+
+        let _effects = _engine.handle(&GameEvent::Command { command: Command::Back }, 0.0);
+        assert_eq!(_engine.state(), PageState::StoryPicker);
+        assert!(_effects.contains(&Effect::CancelJudge));
+
+        // an answer that arrives is not cancelled
+        _engine.select_story(0, 0.0);
+        _engine.handle(&GameEvent::Enter { text: String::from("Do not forget") }, 0.0);
+        let _effects = _engine.handle(&GameEvent::Response { result: Ok(Grade::sample(90)) }, 0.0);
+        assert_eq!(_engine.state(), PageState::Feedback { chapter: 1 });
+        assert!(!_effects.contains(&Effect::CancelJudge));
+    }
+
+    #[test]
+    fn enter_twice_after_feedback_submits_nothing() {
+        let mut _engine = offline();
+        _engine.select_story(0, 0.0);
+        _engine.handle(&GameEvent::Enter { text: _engine.story().chapters[0].answers[0].clone() }, 0.0);
+        assert_eq!(_engine.state(), PageState::Feedback { chapter: 1 });
+        let _enter = GameEvent::Enter { text: String::new() };
+        _engine.handle(&_enter, 0.0);
+        assert!(_engine.handle(&_enter, 0.0).is_empty());
+        assert!(_engine.handle(&GameEvent::Enter { text: String::from("  ") }, 0.0).is_empty());
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 2 });
+        assert!(_engine.session().record(2).unwrap().attempts.is_empty());
+    }
+
+    #[test]
+    fn retry_key_keeps_to_the_attempts() {
+        let mut _engine = offline();
+        _engine.set_settings(Settings { retry_until_correct: true, max_attempts: 2, ..Settings::default() });
+        _engine.select_story(0, 0.0);
+        let _retry = GameEvent::Command { command: Command::Retry };
+        _engine.handle(&GameEvent::Enter { text: String::from("wrong") }, 0.0);
+        assert_eq!(_engine.handle(&_retry, 0.0), vec![Effect::ClearInput]);
+        _engine.handle(&GameEvent::Enter { text: String::from("still wrong") }, 0.0);
+        // the answer has been shown: R does nothing, Enter continues
+        assert!(!_engine.can_retry());
+        assert!(_engine.handle(&_retry, 0.0).is_empty());
+        assert_eq!(_engine.state(), PageState::Feedback { chapter: 1 });
+        assert_eq!(_engine.session().record(1).unwrap().attempts.len(), 2);
+    }
+
+    #[test]
+    fn arrow_keys_choose_among_the_alternatives() {
+        let mut _engine = offline();
+        _engine.select_story(0, 0.0);
+        _engine.handle(&GameEvent::Command { command: Command::ToggleMike }, 0.0);
+        let _alternatives = vec![Alternative { text: String::from("Do not for get"), confidence: 0.4 }];
+        _engine.handle(&GameEvent::Transcript { text: String::from("Do not forge"), confidence: 0.8, alternatives: _alternatives }, 0.0);
+        let _down = GameEvent::Command { command: Command::Down };
+        assert_eq!(_engine.handle(&_down, 0.0), vec![Effect::SetInput(String::from("Do not for get"))]);
+        assert_eq!(_engine.handle(&_down, 0.0), vec![Effect::SetInput(String::from("Do not forge"))]);
+    }
+
+    #[test]
+    fn prompt_contains_the_blank_and_the_answer() {
+        let mut _engine = offline();
+        assert_eq!(_engine.create_prompt("hello"), "");
+        _engine.select_story(0, 0.0);
+        let _prompt = _engine.create_prompt("Do not forget");
+        assert!(_prompt.contains(&_engine.story().chapters[0].blank));
+        assert!(_prompt.contains("Do not forget"));
+    }
+
+    #[test]
+    fn online_judge_leaves_check_to_the_backend() {
+        let mut _engine = Engine::new();
+        _engine.set_api_key(String::from("key"));
+        _engine.select_story(0, 0.0);
+        assert_eq!(_engine.check_answer("anything"), None);
+    }
+
+    #[test]
+    fn retry_mode_repeats_the_chapter_then_shows_the_answer() {
+        let mut _engine = offline();
+        _engine.set_settings(Settings { retry_until_correct: true, max_attempts: 2, ..Settings::default() });
+        _engine.select_story(0, 0.0);
+
+        _engine.submit_answer("wrong", 0.0);
+        _engine.receive_response(Ok(Grade::sample(10)), 0.0);
+        assert!(_engine.must_retry());
+        _engine.click(0.0, 0.0);
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+
+        _engine.submit_answer("still wrong", 0.0);
+        _engine.receive_response(Ok(Grade::sample(20)), 0.0);
+        assert!(!_engine.must_retry());
+        let _answer = &_engine.story().chapters[0].answers[0];
+        assert!(_engine.display_message().ends_with(&format!("Answer: {}", _answer)));
+        _engine.click(0.0, 0.0);
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 2 });
+        assert_eq!(_engine.session().record(1).unwrap().retries(), 1);
+        assert_eq!(_engine.session().record(1).unwrap().score(), Some(20));
+    }
+
+    #[test]
+    fn retry_mode_keeps_the_answer_back_until_the_last_attempt() {
+        let mut _engine = offline();
+        _engine.set_settings(Settings { retry_until_correct: true, max_attempts: 3, ..Settings::default() });
+        _engine.select_story(0, 0.0);
+        let _answer = _engine.story().chapters[0].answers[0].clone();
+        for _attempt in 1..=3 {
+            _engine.handle(&GameEvent::Enter { text: String::from("Don't forget to say") }, 0.0);
+            let _last = _attempt == 3;
+            assert_eq!(_engine.must_retry(), !_last);
+            assert_eq!(_engine.display_message().contains(&_answer), _last);
+            assert_eq!(_engine.display_message().contains("Missing words: good, morning"), _last);
+            let _read = _engine.handle(&GameEvent::Click { x: READ_ALOUD_LEFT, y: 0.0 }, 0.0);
+            assert_eq!(_read.iter().any(|e| matches!(e, Effect::Speak { .. })), _last);
+            if !_last {
+                _engine.handle(&GameEvent::Enter { text: String::new() }, 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn pass_score_decides_the_verdict_and_the_retry() {
+        let mut _engine = offline();
+        _engine.set_settings(Settings { retry_until_correct: true, pass_score: 60, ..Settings::default() });
+        _engine.select_story(0, 0.0);
+        _engine.handle(&GameEvent::Enter { text: String::from("Don't forget to say morning please") }, 0.0);
+        let _grade = _engine.grade().unwrap().clone();
+        assert!((60..80).contains(&_grade.score), "{}", _grade.score);
+        assert!(_grade.correct);
+        assert!(!_engine.must_retry());
+    }
+
+    #[test]
+    fn backend_verdict_follows_the_pass_score() {
+        let mut _engine = Engine::new();
+        _engine.set_api_key(String::from("key"));
+        _engine.set_settings(Settings { retry_until_correct: true, ..Settings::default() });
+        _engine.select_story(0, 0.0);
+        _engine.handle(&GameEvent::Enter { text: String::from("answer") }, 0.0);
+        _engine.handle(&GameEvent::Response { result: Ok(Grade { correct: true, ..Grade::sample(70) }) }, 0.0);
+        assert!(!_engine.grade().unwrap().correct);
+        assert!(_engine.display_message().starts_with("Not quite."));
+        assert!(_engine.must_retry());
+    }
+
+    #[test]
+    fn chapter_without_reference_answers_passes() {
+        let mut _engine = offline();
+        let mut _story = Story::default();
+        _story.chapters[0].answers.clear();
+        _engine.set_stories(vec![_story]);
+        _engine.set_settings(Settings { retry_until_correct: true, ..Settings::default() });
+        _engine.select_story(0, 0.0);
+        _engine.handle(&GameEvent::Enter { text: String::from("anything") }, 0.0);
+        assert!(_engine.grade().is_some_and(|g| g.correct && g.score == 100));
+        assert!(!_engine.must_retry());
+    }
+
+    #[test]
+    fn stale_response_is_ignored() {
+        let mut _engine = offline();
+        _engine.select_story(0, 0.0);
+        assert!(!_engine.receive_response(Ok(Grade::sample(100)), 0.0));
+        assert_eq!(_engine.state(), PageState::ChapterPrompt { chapter: 1 });
+        _engine.receive_chunk("ignored");
+        assert_eq!(_engine.display_message(), "");
+    }
+
+    #[test]
+    fn streamed_chunks_then_error_message() {
+        let mut _engine = offline();
+        _engine.select_story(0, 0.0);
+        _engine.submit_answer("answer", 0.0);
+        _engine.receive_chunk("{\"correct\":");
+        assert_eq!(_engine.display_message(), TEXT_WAITING);
+        _engine.receive_chunk(" false, \"explanation\": \"Say \\\"good");
+        assert_eq!(_engine.display_message(), "Say \"good");
+        _engine.receive_chunk(" morning\\\" \\u304a\\u306f\\u3088\\u3046\", \"score\": 40}");
+        assert_eq!(_engine.display_message(), "Say \"good morning\" おはよう");
+        let _error = GameError::Http(HttpError::Api(ApiError { status: 429, code: String::from("RESOURCE_EXHAUSTED"), message: String::new() }));
+        _engine.receive_response(Err(_error.clone()), 0.0);
+        assert_eq!(_engine.display_message(), _error.user_message());
+        assert_eq!(_engine.grade(), None);
+        assert_eq!(_engine.session().record(1).unwrap().score(), None);
+    }
+
+    #[test]
+    fn saved_key_is_only_sent_where_it_was_entered() {
+        let mut _engine = Engine::new();
+        _engine.set_api_key(String::from("secret"));
+        let _headers = |engine: &Engine| engine.judge().unwrap().request("prompt").unwrap().headers;
+        assert!(_headers(&_engine).iter().any(|(_, value)| value == "secret"));
+
+        // a link to another endpoint asks for a key instead of using the saved one
+        let _saved = ApiKey::new("secret", _engine.judge_settings());
+        for _backend in [Backend::Gemini, Backend::OpenAi] {
+            let mut _engine = Engine::new();
+            _engine.restore_api_key(_saved.clone());
+            _engine.set_judge_settings(JudgeSettings { endpoint: String::from("https://attacker.example/"), ..JudgeSettings::new(_backend) });
+            assert!(!_engine.has_judge_key());
+            assert!(_headers(&_engine).iter().all(|(_, value)| !value.contains("secret")));
+            _engine.select_story(0, 0.0);
+            assert_eq!(_engine.state(), PageState::ApiKeyEntry);
+
+            // the next visit keeps the endpoint, and still not the key
+            let mut _next = Engine::new();
+            _next.restore(_engine.snapshot());
+            _next.restore_api_key(_saved.clone());
+            assert_eq!(_next.judge_settings().endpoint, "https://attacker.example/");
+            assert!(!_next.has_judge_key());
+        }
+    }
+
+    #[test]
+    fn restore_asks_again_for_a_lost_request() {
+        let mut _engine = offline();
+        _engine.select_story(0, 0.0);
+        _engine.submit_answer("answer", 0.0);
+        let _snapshot = _engine.snapshot();
+
+        let mut _restored = Engine::new();
+        _restored.restore(_snapshot.clone());
+        assert_eq!(_restored.state(), PageState::ChapterPrompt { chapter: 1 });
+        assert_eq!(_restored.judge_settings().backend, Backend::Offline);
+        assert_eq!(_restored.session(), _engine.session());
+
+        let mut _invalid = _snapshot;
+        _invalid.state = PageState::Feedback { chapter: 99 };
+        _restored.restore(_invalid);
+        assert_eq!(_restored.state(), PageState::StoryPicker);
+    }
+}
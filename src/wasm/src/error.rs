@@ -0,0 +1,225 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+// Errors of the game; DOM and canvas failures are shown on the canvas instead of panicking.
+// Plain data without web_sys, so the engine builds natively; the JsValue conversions are in the web adapter
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameError {
+    ElementMissing(String),         // id of the element not found in the page
+    ContextUnavailable,             // canvas 2d context
+    Http(HttpError),
+    Json(String),
+    Speech(String),                 // SpeechRecognitionErrorEvent.error
+    Js(String),                     // any other exception thrown by a DOM call
+}
+
+pub type Result<T, E = GameError> = std::result::Result<T, E>;
+
+impl GameError {
+    // message for the player
+
+    pub fn user_message(&self) -> String {
+        match self {
+            GameError::Http(e) => e.user_message(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::ElementMissing(id) => write!(f, "Element #{} is missing from the page", id),
+            GameError::ContextUnavailable => write!(f, "Canvas 2d context is unavailable"),
+            GameError::Http(e) => write!(f, "{}", e),
+            GameError::Json(e) => write!(f, "JSON Error: {}", e),
+            GameError::Speech(e) => write!(f, "Speech recognition error: {}", e),
+            GameError::Js(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+impl From<HttpError> for GameError {
+    fn from(e: HttpError) -> Self {
+        GameError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for GameError {
+    fn from(e: serde_json::Error) -> Self {
+        GameError::Json(e.to_string())
+    }
+}
+
+// HTTP errors of the judge backends
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum HttpError {
+    Network(String),
+    Timeout,
+    Api(ApiError),
+    Aborted,
+    Parse(String),
+}
+
+impl HttpError {
+    // worth sending again after a pause
+
+    pub fn retryable(&self) -> bool {
+        match self {
+            HttpError::Network(_) | HttpError::Timeout => true,
+            HttpError::Api(e) => e.status == 429 || e.status >= 500,
+            _ => false,
+        }
+    }
+
+    // message for the player
+
+    pub fn user_message(&self) -> String {
+        match self {
+            HttpError::Network(_) => String::from("Network error. Check your internet connection and try again."),
+            HttpError::Timeout => String::from("The request timed out. Try again."),
+            HttpError::Api(e) if e.is_invalid_key() => String::from("Invalid API key. Forget the key on the story list and enter a new one."),
+            HttpError::Api(e) if e.is_quota() => String::from("Quota exhausted or too many requests. Wait a while and try again."),
+            HttpError::Api(e) if e.status >= 500 => format!("The server is unavailable ({}). Try again later.", e.status),
+            HttpError::Api(e) => e.to_string(),
+            HttpError::Aborted | HttpError::Parse(_) => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Network(e) => write!(f, "Network Error: {}", e),
+            HttpError::Timeout => write!(f, "Request timed out"),
+            HttpError::Api(e) => write!(f, "{}", e),
+            HttpError::Aborted => write!(f, "Request cancelled"),
+            HttpError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// error response: {"error": {"code": 400, "message": "...", "status": "INVALID_ARGUMENT"}} (Gemini),
+// {"error": {"message": "...", "type": "...", "code": "..."}} (OpenAI), {"error": "..."} (Ollama)
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub status: u16,        // HTTP status
+    pub code: String,       // e.g. RESOURCE_EXHAUSTED, empty when not given
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorDetail {
+    Object {
+        #[serde(default)]
+        message: String,
+        #[serde(default)]
+        status: Option<String>,
+        #[serde(default, rename = "type")]
+        kind: Option<String>,
+    },
+    Text(String),
+}
+
+impl ApiError {
+    pub fn from_response(status: u16, body: &str) -> Self {
+        let (_code, _message) = match serde_json::from_str::<ErrorBody>(body).map(|b| b.error) {
+            Ok(ErrorDetail::Object { message, status, kind }) => (status.or(kind).unwrap_or_default(), message),
+            Ok(ErrorDetail::Text(message)) => (String::new(), message),
+            Err(_) => (String::new(), String::new()),
+        };
+        ApiError { status, code: _code, message: _message }
+    }
+
+    pub fn is_invalid_key(&self) -> bool {
+        // Gemini answers 400 INVALID_ARGUMENT "API key not valid", OpenAI 401 "Incorrect API key"
+        matches!(self.status, 401 | 403)
+            || matches!(self.code.as_str(), "UNAUTHENTICATED" | "PERMISSION_DENIED")
+            || self.message.contains("API key")
+    }
+
+    pub fn is_quota(&self) -> bool {
+        self.status == 429 || matches!(self.code.as_str(), "RESOURCE_EXHAUSTED" | "insufficient_quota")
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.code.is_empty(), self.message.is_empty()) {
+            (_, true) => write!(f, "HTTP Error: {}", self.status),
+            (true, false) => write!(f, "HTTP Error: {} {}", self.status, self.message),
+            (false, false) => write!(f, "HTTP Error: {} {} {}", self.status, self.code, self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api(status: u16, body: &str) -> HttpError {
+        HttpError::Api(ApiError::from_response(status, body))
+    }
+
+    #[test]
+    fn gemini_errors() {
+        let _invalid = ApiError::from_response(400, r#"{"error": {"code": 400, "message": "API key not valid. Please pass a valid API key.", "status": "INVALID_ARGUMENT"}}"#);
+        assert_eq!(_invalid.code, "INVALID_ARGUMENT");
+        assert!(_invalid.is_invalid_key() && !_invalid.is_quota());
+
+        let _quota = ApiError::from_response(429, r#"{"error": {"code": 429, "message": "Resource has been exhausted (e.g. check quota).", "status": "RESOURCE_EXHAUSTED"}}"#);
+        assert!(_quota.is_quota() && !_quota.is_invalid_key());
+
+        let _overloaded = api(503, r#"{"error": {"code": 503, "message": "The model is overloaded.", "status": "UNAVAILABLE"}}"#);
+        assert!(_overloaded.retryable());
+        assert_eq!(_overloaded.user_message(), "The server is unavailable (503). Try again later.");
+
+        let _bad_request = api(400, r#"{"error": {"code": 400, "message": "Invalid JSON payload", "status": "INVALID_ARGUMENT"}}"#);
+        assert!(!_bad_request.retryable());
+        assert_eq!(_bad_request.user_message(), "HTTP Error: 400 INVALID_ARGUMENT Invalid JSON payload");
+    }
+
+    #[test]
+    fn openai_errors() {
+        let _invalid = api(401, r#"{"error": {"message": "Incorrect API key provided: sk-****.", "type": "invalid_request_error", "param": null, "code": "invalid_api_key"}}"#);
+        assert!(!_invalid.retryable());
+        assert!(_invalid.user_message().starts_with("Invalid API key."));
+
+        let _quota = ApiError::from_response(429, r#"{"error": {"message": "You exceeded your current quota.", "type": "insufficient_quota", "param": null, "code": "insufficient_quota"}}"#);
+        assert_eq!(_quota.code, "insufficient_quota");
+        assert!(_quota.is_quota() && !_quota.is_invalid_key());
+        assert!(HttpError::Api(_quota).user_message().starts_with("Quota exhausted"));
+    }
+
+    #[test]
+    fn ollama_and_unparsed_errors() {
+        let _missing = ApiError::from_response(404, r#"{"error": "model \"llama9\" not found, try pulling it first"}"#);
+        assert_eq!(_missing.code, "");
+        assert!(!_missing.is_invalid_key() && !_missing.is_quota());
+        assert_eq!(HttpError::Api(_missing).user_message(), "HTTP Error: 404 model \"llama9\" not found, try pulling it first");
+
+        let _gateway = api(502, "<html>Bad Gateway</html>");
+        assert!(_gateway.retryable());
+        assert_eq!(_gateway, HttpError::Api(ApiError { status: 502, code: String::new(), message: String::new() }));
+    }
+
+    #[test]
+    fn network_errors_are_retried_cancels_are_not() {
+        assert!(HttpError::Network(String::from("Failed to fetch")).retryable());
+        assert!(HttpError::Timeout.retryable());
+        assert!(!HttpError::Aborted.retryable());
+        assert!(HttpError::Network(String::new()).user_message().starts_with("Network error."));
+        assert!(HttpError::Timeout.user_message().starts_with("The request timed out."));
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+use crate::judge::{ApiKey, Grade};
+use crate::settings::SpeechSettings;
+use crate::engine::{Engine, Snapshot};
+use crate::error::*;
+
+// Every input of the game, as data: the browser callbacks turn into GameEvents,
+// which are recorded with their time and can be replayed against an Engine
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+    Frame,                              // animation frame, not recorded
+    Click { x: f64, y: f64 },           // canvas or message box
+    Enter { text: String },             // Enter key, with the sanitized answer box text
+    Transcript {                        // final speech recognition result
+        text: String,
+        #[serde(default)] confidence: f64,
+        #[serde(default)] alternatives: Vec<Alternative>,   // the other ones, next best first
+    },
+    InterimTranscript { text: String, confidence: f64 },              // what the recognizer is hearing, not recorded
+    SpeechProcessing,                   // the player stopped speaking (speechend)
+    SpeechError { error: String },
+    SpeechEnd,                          // the recognizer has stopped
+    ResponseChunk { text: String },     // streamed judge text
+    Response { result: Result<Grade> }, // judge answer
+    Command { command: Command },       // key other than Enter
+    NarrationEnd { narration: u32, sentence: usize },   // a sentence of the chapter has been spoken
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alternative {
+    pub text: String,
+    pub confidence: f64,                // 0.0 - 1.0
+}
+
+// keymap: KeyboardEvent.key to what it does
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    Continue,                           // Space
+    Back,                               // Escape: stop listening or reading, else to the story list
+    ToggleMike,                         // M
+    Retry,                              // R
+    ReadAloud,                          // N: the read aloud button, start or stop
+    Up,                                 // ArrowUp: story list and speech alternatives
+    Down,                               // ArrowDown
+}
+
+impl Command {
+    pub fn from_key(key: &str) -> Option<Command> {
+        match key {
+            " " | "Spacebar" => Some(Command::Continue),
+            "Escape" | "Esc" => Some(Command::Back),
+            "m" | "M" => Some(Command::ToggleMike),
+            "r" | "R" => Some(Command::Retry),
+            "n" | "N" => Some(Command::ReadAloud),
+            "ArrowUp" | "Up" => Some(Command::Up),
+            "ArrowDown" | "Down" => Some(Command::Down),
+            _ => None,
+        }
+    }
+}
+
+// streamed chunks are left out: the final Response carries the whole answer
+
+impl GameEvent {
+    pub fn is_recorded(&self) -> bool {
+        !matches!(self, GameEvent::Frame | GameEvent::InterimTranscript { .. } | GameEvent::ResponseChunk { .. })
+    }
+}
+
+// What the page asks of the browser after an event
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    ClearInput,
+    SetInput(String),
+    SetTentativeInput(String),          // interim transcript, marked until the final one
+    SaveApiKey(ApiKey),                 // bound to the backend and endpoint it was entered for
+    ForgetApiKey,
+    StartRecognition,
+    StopRecognition,                    // the result of what was said so far still comes
+    ConfigureSpeech(SpeechSettings),    // language and options of the recognizer changed
+    Judge { prompt: String },           // send to the judge backend, answered with Response
+    CancelJudge,                        // abort the request in flight, no Response follows
+    Speak { text: String, narration: u32, sentence: Option<usize> },    // chapter sentences are answered with NarrationEnd
+    CancelSpeech,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimedEvent {
+    pub time: f64,                      // Date.now()
+    pub event: GameEvent,
+}
+
+// Events since a snapshot; the API key itself is never recorded
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventLog {
+    pub snapshot: Snapshot,
+    pub has_api_key: bool,
+    pub events: Vec<TimedEvent>,
+}
+
+pub const MAX_EVENTS: usize = 1000;
+pub const REDACTED: &str = "********";
+
+impl EventLog {
+    pub fn new(engine: &Engine) -> Self {
+        EventLog {
+            snapshot: engine.snapshot(),
+            has_api_key: engine.has_judge_key(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, time: f64, event: GameEvent) {
+        self.events.push(TimedEvent { time, event });
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.events.len() >= MAX_EVENTS
+    }
+
+    pub fn from_json(json: &str) -> Result<EventLog> {
+        Ok(serde_json::from_str::<EventLog>(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+pub type MockJudge<'a> = &'a mut dyn FnMut(&str) -> Result<Grade>;   // prompt -> grade
+
+// Replays the log on the engine (the engine's stories must match the recording).
+// With a judge, recorded responses are skipped and every Judge effect is answered by it;
+// without, the recorded responses are played back. Returns the effects of all events
+
+pub fn replay(engine: &mut Engine, log: &EventLog, mut judge: Option<MockJudge>) -> Vec<Effect> {
+    engine.restore(log.snapshot.clone());
+    engine.set_api_key(if log.has_api_key { String::from(REDACTED) } else { String::new() });
+
+    let mut _effects = Vec::new();
+    for _timed in log.events.iter() {
+        let _mocked = matches!(_timed.event, GameEvent::Response { .. } | GameEvent::ResponseChunk { .. });
+        if _mocked && judge.is_some() { continue; }
+        for _effect in engine.handle(&_timed.event, _timed.time) {
+            if let (Effect::Judge { prompt }, Some(_judge)) = (&_effect, judge.as_mut()) {
+                let _response = GameEvent::Response { result: _judge(prompt) };
+                _effects.extend(engine.handle(&_response, _timed.time));
+            }
+            _effects.push(_effect);
+        }
+    }
+    _effects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::*;
+    use crate::engine::Recognizer;
+
+    // picker -> key -> every chapter answered -> scoreboard
+
+    fn script(engine: &Engine) -> EventLog {
+        let mut _log = EventLog::new(engine);
+        let mut _time = 0.0;
+        let mut _push = |event: GameEvent| {
+            _time += 1000.0;
+            _log.push(_time, event);
+        };
+        _push(GameEvent::Enter { text: String::from("1") });
+        _push(GameEvent::Enter { text: String::from(REDACTED) });
+        for _chapter in engine.story().chapters.iter() {
+            _push(GameEvent::Click { x: 300.0, y: 300.0 });
+            _push(GameEvent::InterimTranscript { text: String::from("Do"), confidence: 0.0 });
+            _push(GameEvent::Transcript { text: _chapter.answers[0].clone(), confidence: 0.9, alternatives: Vec::new() });
+            _push(GameEvent::SpeechEnd);
+            _push(GameEvent::Enter { text: _chapter.answers[0].clone() });
+            _push(GameEvent::Click { x: 300.0, y: 300.0 });
+        }
+        _log
+    }
+
+    #[test]
+    fn scripted_playthrough_with_mocked_judge() {
+        let mut _engine = Engine::new();
+        let _log = script(&_engine);
+        let mut _prompts = Vec::new();
+        let mut _judge = |prompt: &str| {
+            _prompts.push(prompt.to_string());
+            Ok(Grade::sample(90))
+        };
+        let _effects = replay(&mut _engine, &_log, Some(&mut _judge));
+
+        let _chapters = _engine.story().chapters.len();
+        assert_eq!(_engine.state(), PageState::Finished);
+        assert_eq!(_prompts.len(), _chapters);
+        assert!(_prompts[0].contains(&_engine.story().chapters[0].blank));
+        assert_eq!(_engine.session().total_score(), 90 * _chapters as u32);
+        assert!(_effects.contains(&Effect::SaveApiKey(ApiKey::new(REDACTED, _engine.judge_settings()))));
+        assert_eq!(_effects.iter().filter(|e| **e == Effect::StartRecognition).count(), _chapters);
+        assert_eq!(_engine.recognizer(), &Recognizer::Idle);
+    }
+
+    #[test]
+    fn recorded_responses_replay_identically() {
+        let mut _engine = Engine::new();
+        let mut _log = EventLog::new(&_engine);
+        _log.push(1.0, GameEvent::Enter { text: String::from("1") });
+        _log.push(2.0, GameEvent::Enter { text: String::from(REDACTED) });
+        _log.push(3.0, GameEvent::Enter { text: String::from("Do not forget") });
+        _log.push(4.0, GameEvent::ResponseChunk { text: String::from("{\"correct\"") });
+        _log.push(5.0, GameEvent::Response { result: Ok(Grade::sample(55)) });
+
+        let _json = _log.to_json().unwrap();
+        let _restored = EventLog::from_json(&_json).unwrap();
+        assert_eq!(_restored, _log);
+
+        let _effects = replay(&mut _engine, &_restored, None);
+        assert_eq!(_engine.state(), PageState::Feedback { chapter: 1 });
+        assert_eq!(_engine.session().record(1).unwrap().score(), Some(55));
+        assert!(_effects.iter().any(|e| matches!(e, Effect::Judge { .. })));
+    }
+
+    #[test]
+    fn failed_response_is_replayed_as_error_message() {
+        let mut _engine = Engine::new();
+        _engine.set_api_key(String::from("key"));
+        let mut _log = EventLog::new(&_engine);
+        _log.push(1.0, GameEvent::Enter { text: String::from("1") });
+        _log.push(2.0, GameEvent::Enter { text: String::from("answer") });
+        let _error = GameError::Http(HttpError::Timeout);
+        _log.push(3.0, GameEvent::Response { result: Err(_error.clone()) });
+
+        let _restored = EventLog::from_json(&_log.to_json().unwrap()).unwrap();
+        replay(&mut _engine, &_restored, None);
+        assert_eq!(_engine.display_message(), _error.user_message());
+    }
+
+    #[test]
+    fn frames_are_not_recorded() {
+        assert!(!GameEvent::Frame.is_recorded());
+        assert!(!GameEvent::InterimTranscript { text: String::from("Do"), confidence: 0.5 }.is_recorded());
+        assert!(!GameEvent::ResponseChunk { text: String::from("{\"correct\"") }.is_recorded());
+        assert!(GameEvent::SpeechEnd.is_recorded());
+    }
+}
@@ -1,6 +1,14 @@
 use crate::common::*;
-use wasm_bindgen::JsCast;
-use web_sys::{HtmlCanvasElement, HtmlImageElement, CanvasRenderingContext2d, Document, HtmlInputElement, HtmlTextAreaElement, HtmlElement, MouseEvent};
+use crate::story::*;
+use crate::judge::*;
+use crate::settings::*;
+use crate::storage::*;
+use crate::engine::*;
+use crate::error::*;
+use crate::render::*;
+use crate::event::*;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{HtmlCanvasElement, HtmlImageElement, CanvasRenderingContext2d, Document, HtmlInputElement, HtmlTextAreaElement};
 
 macro_rules! log {
     ( $( $t:tt )* ) => {
@@ -8,427 +16,401 @@ macro_rules! log {
     }
 }
 
+// Web adapter of the Engine: DOM, canvas, localStorage and the clock
+
+impl From<JsValue> for GameError {
+    fn from(e: JsValue) -> Self {
+        GameError::Js(e.as_string().unwrap_or_else(|| format!("{:?}", e)))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Game{
     document: Document,
     image: HtmlImageElement,
-    page: usize,
-    message: String,
-    api_endpoint: String,
-    mike: bool,
+    engine: Engine,
+    error: Option<GameError>,
+    events: Option<EventLog>,   // recording, once the saved session and settings are applied
 }
 pub trait StaticGame {
-    fn new(document: Document) -> Self;
-    fn set_image(&mut self, image:HtmlImageElement);
-    fn set_message(&mut self, text:String);
-    fn set_api_endpoint(&mut self, _api_endpoint:String);
-    fn set_mike_on(&mut self);
-    fn set_mike_off(&mut self);
-    fn set_page(&mut self, page:usize);
-    fn next_page(&mut self);
+    fn new(document: Document) -> Result<Self> where Self: Sized;
+    fn set_stories(&mut self, stories:Vec<Story>);
+    fn restore_api_key(&mut self, api_key:ApiKey);
+    fn set_judge_settings(&mut self, judge:JudgeSettings);
+    fn set_settings(&mut self, settings:Settings);
+    fn set_speech_available(&mut self, available:bool);
+    fn restore(&mut self, snapshot:Snapshot);
+    fn start_recording(&mut self);
+    fn replay(&mut self, events:&EventLog);
+    fn dispatch(&mut self, event:GameEvent) -> Vec<Effect>;
     fn on_animation_frame(&mut self);
     fn on_image(&mut self, _image: HtmlImageElement);
-    fn on_http_request(&mut self, response: String);
-    fn on_click(&mut self); 
-    fn on_input_changed(&mut self, transcript: &String);
+    fn on_error(&mut self, error: GameError);
     fn get_document(&self) -> Document;
-    fn get_canvas(&self) -> HtmlCanvasElement;
-    fn get_context(&self) -> CanvasRenderingContext2d;
-    fn get_message(&self) -> String;
-    fn get_page(&self) -> usize;
-    fn get_page_type(&self) -> PageType;
-    fn get_api_endpoint(&self) -> String;
-    fn get_mike_status(&self) -> bool;
-    fn update(&mut self);
-    fn draw(&self);
-    fn clear(&self);
-    fn create_prompt(&self, _text:String) -> String;
+    fn get_canvas(&self) -> Result<HtmlCanvasElement>;
+    fn get_context(&self) -> Result<CanvasRenderingContext2d>;
+    fn get_input(&self) -> Result<HtmlInputElement>;
+    fn get_textarea(&self) -> Result<HtmlTextAreaElement>;
+    fn get_grade(&self) -> Option<Grade>;
+    fn must_retry(&self) -> bool;
+    fn get_story(&self) -> &Story;
+    fn get_state(&self) -> PageState;
+    fn get_judge(&self) -> Option<Box<dyn TranslationJudge>>;
+    fn get_settings(&self) -> Settings;
+    fn update(&mut self) -> Result<()>;
+    fn draw(&self) -> Result<()>;
+    fn clear(&self) -> Result<()>;
 }
 
 impl StaticGame for Game{
 
     // init
 
-    fn new(document: Document) -> Self{
-        let _canvas = document.get_element_by_id("canvas").unwrap().dyn_into::<HtmlCanvasElement>().unwrap();
-        let _screen_width = _canvas.client_width() as f32;
-        let _screen_height = _canvas.client_height() as f32;
-        let _image = HtmlImageElement::new().unwrap();
+    fn new(document: Document) -> Result<Self>{
+        let _image = HtmlImageElement::new()?;
 
-        Game {
-            document: document,
+        let _game = Game {
+            document,
             image: _image,
-            page: 0,
-            message: String::from(""),
-            api_endpoint: String::from(""),
-            mike: false,
-        }
+            engine: Engine::new(),
+            error: None,
+            events: None,
+        };
+        // nothing can be shown without the canvas
+        _game.get_context()?;
+        Ok(_game)
     }
 
     fn get_document(&self) -> Document{
         self.document.clone()
     }
     
-    fn get_canvas(&self) -> HtmlCanvasElement{
-        let _canvas = self.document.get_element_by_id("canvas").unwrap();
-        _canvas.dyn_into::<HtmlCanvasElement>().unwrap()
+    fn get_canvas(&self) -> Result<HtmlCanvasElement>{
+        self.element::<HtmlCanvasElement>("canvas")
     }
-    fn get_context(&self) -> CanvasRenderingContext2d{
-        self.get_canvas().get_context("2d").unwrap().unwrap().dyn_into::<CanvasRenderingContext2d>().unwrap()
+    fn get_context(&self) -> Result<CanvasRenderingContext2d>{
+        self.get_canvas()?.get_context("2d")?
+            .and_then(|c| c.dyn_into::<CanvasRenderingContext2d>().ok())
+            .ok_or(GameError::ContextUnavailable)
     }
-    fn get_api_endpoint(&self) -> String {
-        self.api_endpoint.clone()
+    fn get_input(&self) -> Result<HtmlInputElement>{
+        self.element::<HtmlInputElement>("input")
     }
-    fn get_message(&self) -> String {
-        self.message.clone()
+    fn get_textarea(&self) -> Result<HtmlTextAreaElement>{
+        self.element::<HtmlTextAreaElement>("mytextarea")
     }
-    fn get_page(&self) -> usize {
-        self.page.clone()
+    fn get_judge(&self) -> Option<Box<dyn TranslationJudge>> {
+        self.engine.judge()
     }
-    fn get_page_type(&self) -> PageType{
-        if self.page == 0 { return PageType::First; }
-        if self.page == LAST_PAGE { return PageType::Fin; }
-        let _p = self.page % 2;
-        match _p {
-            0 => { PageType::Output },
-            _ => { PageType::Input }
-        }
+    fn get_settings(&self) -> Settings {
+        self.engine.settings().clone()
     }
-    fn get_mike_status(&self) -> bool {
-        self.mike.clone()
+    fn get_grade(&self) -> Option<Grade> {
+        self.engine.grade().cloned()
     }
-    fn set_image(&mut self, image:HtmlImageElement){
-        self.image = image;
+    fn must_retry(&self) -> bool {
+        self.engine.must_retry()
     }
-    fn set_message(&mut self, message:String){
-        self.message = message;
+    fn get_story(&self) -> &Story {
+        self.engine.story()
     }
-    fn set_api_endpoint(&mut self, api_endpoint:String){
-        self.api_endpoint = api_endpoint;
-        self.next_page();
+    fn get_state(&self) -> PageState {
+        self.engine.state()
     }
-    fn set_mike_on(&mut self){
-        self.mike = true;
+    fn set_stories(&mut self, stories:Vec<Story>){
+        self.engine.set_stories(stories);
     }
-    fn set_mike_off(&mut self){
-        self.mike = false;
+    fn restore_api_key(&mut self, api_key:ApiKey){
+        self.engine.restore_api_key(api_key);
     }
-    fn set_page(&mut self, page:usize){
-        self.page = page;
+    fn set_judge_settings(&mut self, judge:JudgeSettings){
+        self.engine.set_judge_settings(judge);
+        self.save();
     }
-    fn next_page(&mut self) {
-        match self.get_page_type() {
-            PageType::First => {
-                self.page +=  1;
-            },
-            PageType::Fin => {
-                self.page = 0;
-            }
-            _ => {
-                self.page += 1;
-            }
-        }
+    fn set_settings(&mut self, settings:Settings){
+        self.engine.set_settings(settings);
+        self.save();
+    }
+    fn set_speech_available(&mut self, available:bool){
+        self.engine.set_speech_available(available);
+    }
+    fn restore(&mut self, snapshot:Snapshot){
+        self.engine.restore(snapshot);
+    }
+    fn start_recording(&mut self){
+        self.events = Some(EventLog::new(&self.engine));
     }
 
-    // Speech recognition result
+    // reproduce a recorded session with its recorded answers
 
-    fn on_input_changed(&mut self, transcript: &String){
-        let _document = &self.get_document();
-        let _input = _document.get_element_by_id("input").unwrap();
-        let _text = _input.dyn_into::<HtmlInputElement>().unwrap();
-        let _= _text.set_value(&transcript);
-        let _= _text.focus();
-        let _= self.set_mike_off();
+    fn replay(&mut self, events:&EventLog){
+        let _effects = replay(&mut self.engine, events, None);
+        // the log only knows whether a key was set
+        match load_api_key() {
+            Some(_api_key) => self.engine.restore_api_key(_api_key),
+            None => self.engine.forget_api_key(),
+        }
+        log!("Replayed {} events: {:?}", events.events.len(), self.engine.state());
+        for _effect in _effects.iter().filter(|e| matches!(e, Effect::ClearInput | Effect::SetInput(_) | Effect::SetTentativeInput(_))) {
+            if let Err(e) = self.apply(_effect) {
+                self.on_error(e);
+            }
+        }
+        self.save();
     }
 
-    // Gemini Prompt
+    // every input: recorded, handled by the engine, then the page effects applied.
+    // effects for the game loop (speech, HTTP) are returned
 
-    fn create_prompt(&self, _text:String) -> String {
-        let _page = self.page;
-        match _page {
-            1 .. 18 => {
-                let _chapter = (_page + 1) / 2;
-                format!("{} の英訳は、{} で正しいですか？", TEXT_CHAPTER_TEXT_PLACEHOLDER[_chapter], _text)  
-            },
-            _ => {
-                String::from("")
+    fn dispatch(&mut self, event:GameEvent) -> Vec<Effect> {
+        if let GameEvent::Frame = event {
+            self.on_animation_frame();
+            return Vec::new();
+        }
+        if let GameEvent::SpeechError { error } = &event {
+            self.on_error(GameError::Speech(error.clone()));
+        }
+        let _now = js_sys::Date::now();
+        self.record(&event, _now);
+        let _state = self.engine.state();
+        let _effects = self.engine.handle(&event, _now);
+        if self.engine.state() != _state {
+            log!("{:?} -> {:?}", _state, self.engine.state());
+        }
+        // nothing worth saving changes with an interim transcript or a streamed chunk
+        if event.is_recorded() {
+            self.save();
+        }
+
+        let mut _rest = Vec::new();
+        for _effect in _effects {
+            match self.apply(&_effect) {
+                Ok(true) => {},
+                Ok(false) => _rest.push(_effect),
+                Err(e) => self.on_error(e),
             }
         }
+        _rest
     }
 
-    // callback image load
+    // errors of callbacks that return nothing: logged once
 
-    fn on_image(&mut self, _image: HtmlImageElement) {
-        let _= self.set_image(_image);
+    fn on_error(&mut self, error: GameError) {
+        if self.error.as_ref() != Some(&error) {
+            log!("{}", error);
+        }
+        self.error = Some(error);
     }
 
-    // callback http request
+    // callback image load
 
-    fn on_http_request(&mut self, response: String) {
-        self.set_message(response);
+    fn on_image(&mut self, _image: HtmlImageElement) {
+        self.image = _image;
     }
 
     // game controller
 
-    fn update(&mut self){
-        let _image = HtmlImageElement::new().unwrap();
-        self.clear();
-        self.draw();
+    fn update(&mut self) -> Result<()>{
+        self.clear()?;
+        if let Err(e) = self.draw() {
+            // error screen rather than a panic, e.g. when #input is missing
+            self.clear()?;
+            self.draw_error(&e)?;
+            return Err(e);
+        }
+        Ok(())
     }
 
     // callback animation
 
     fn on_animation_frame(&mut self) {
-        self.update();
+        match self.update() {
+            Ok(()) => self.error = None,
+            Err(e) => self.on_error(e),
+        }
     }
 
-    // callback click: controll page number
+    // draw
+
+    fn draw(&self) -> Result<()>{
+        // the form first: a missing #input or #mytextarea shows the error screen instead
+        self.draw_form()?;
+        let _context = self.get_context()?;
+        draw_page(&self.engine, &mut CanvasRenderer::new(&_context, &self.image), &self.screen()?);
+        Ok(())
+    }
 
-    fn on_click(&mut self) {
+    // clear screen
 
-        // Mike Display ON/OFF
+    fn clear(&self) -> Result<()>{
+        let _context = self.get_context()?;
+        CanvasRenderer::new(&_context, &self.image).clear();
+        Ok(())
+    }
+}
 
-        let _page_type = self.get_page_type();
+impl Game {
 
-        match _page_type {
-            PageType::First => {
-                return;
-            },
-            PageType::Input => {
-                let _= self.set_mike_on();
-            },
-            PageType::Fin => {
-                let _document = &self.get_document();
-                let _input = _document.get_element_by_id("input").unwrap();
-                let _text = _input.dyn_into::<HtmlInputElement>().unwrap();
-                let _= _text.set_value("");
-                let _= self.set_message(String::from(""));
-                let _= self.set_page(0);
-            },
-            PageType::Output => {
-                let _document = &self.get_document();
-                let _input = _document.get_element_by_id("input").unwrap();
-                let _text = _input.dyn_into::<HtmlInputElement>().unwrap();
-                let _= _text.set_value("");
-                let _= self.set_message(String::from(""));
-                let _= self.set_mike_off();
-                self.next_page();
-            }
-        }
+    fn element<T: JsCast>(&self, id: &'static str) -> Result<T> {
+        self.document.get_element_by_id(id)
+            .and_then(|e| e.dyn_into::<T>().ok())
+            .ok_or_else(|| GameError::ElementMissing(id.to_string()))
     }
 
-    // draw
+    fn screen(&self) -> Result<Screen> {
+        let _canvas = self.get_canvas()?;
+        Ok(Screen {
+            width: _canvas.width() as f64,
+            height: _canvas.height() as f64,
+            top: _canvas.client_top() as f64,
+            left: _canvas.client_left() as f64,
+        })
+    }
 
-    fn draw(&self){
+    // error screen: only needs the canvas
 
-        // Get Page
-        let _context = self.get_context();
-        let _page = self.get_page();
-        let _chapter = (_page + 1) / 2; // page:1,2 -> chapter:1,  page:3,4 -> chapter:2 ...
-        let _page_type = self.get_page_type();
+    fn draw_error(&self, error: &GameError) -> Result<()> {
+        let _context = self.get_context()?;
+        let _screen = self.screen()?;
+        draw_error(&mut CanvasRenderer::new(&_context, &self.image), error, &_screen);
+        Ok(())
+    }
 
-        // Get Screen
-        let _canvas = self.document.get_element_by_id("canvas").unwrap().dyn_into::<HtmlCanvasElement>().unwrap();
-        let _canvas_width = self.get_canvas().width() as f64;
-        let _canvas_height = self.get_canvas().height() as f64;
-        let _canvas_top = self.get_canvas().client_top() as f64;
-        let _canvas_left = self.get_canvas().client_left() as f64;
-        let _canvas_offsetleft = (self.get_canvas().client_left() + self.get_canvas().offset_left()) as f64;
+    // answer box and message box of the page
+
+    fn draw_form(&self) -> Result<()> {
+        let _state = self.get_state();
+        let _canvas = self.get_canvas()?;
+        let _canvas_width = _canvas.width() as f64;
+        let _canvas_height = _canvas.height() as f64;
+        let _canvas_top = _canvas.client_top() as f64;
+        let _canvas_offsetleft = (_canvas.client_left() + _canvas.offset_left()) as f64;
 
         // Get InputText
-        let _document = &self.get_document();
-        let _input = _document.get_element_by_id("input").unwrap();
-        let _input_element = _input.dyn_into::<HtmlInputElement>().unwrap();
+        let _input_element = self.get_input()?;
         let _= _input_element.style().set_property("background-color", "#D9FFB3");
 
         // Get Textarea
-        let _mytextarea = _document.get_element_by_id("mytextarea").unwrap();
-        let _textarea = _mytextarea.dyn_into::<HtmlElement>().unwrap();
-        let _textarea_cloned = _textarea.clone();
-        let _textarea_message = _textarea.dyn_into::<HtmlTextAreaElement>().unwrap();
-
-        match _page_type {
-
-            // Opening
-
-            PageType::First => {
-                // Title
-                let _= _context.set_fill_style_str(DEFAULT_COLOR);
-                let _= _context.set_font("36px MyFont");
-                let _= _context.set_text_align("center");
-                let _= _context.fill_text(TITLE, _canvas_width / 2.0, 90.0);
-                let _= _context.set_font("18px MyFont");
-                let _lines: Vec<&str> = TEXT_OPEN.split('\n').collect();
-                // Intro 
-                for i in 0.._lines.len(){
-                    let _= _context.fill_text(_lines[i], _canvas_width / 2.0, (130.0 + (TEXT_SPACE * i) as f32).into());
-                }
-                // Mike
-                let _= _context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                    &self.image, 120.0, 900.0, 60.0,150.0, _canvas_width / 2.0 - 120.0 / 2.0, 210.0, 120.0, 300.0);
-                // LITTLE RED RIDING HOOD & WOLFS
-                let _= _context.set_stroke_style_str("rgba(-1,128, 0)");
-                let _= _context.begin_path();
-                let _= _context.close_path();
-                let _= _context.stroke();
-                let _= _context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                    &self.image, 0.0,0.0,120.0,150.0,-35.0,340.0,240.0,300.0);
-                let _ =  _context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                    &self.image, 0.0,150.0,120.0,150.0,165.0,360.0,260.0,320.0);
-                let _ =  _context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                    &self.image, 120.0,0.0,120.0,150.0, 385.0,340.0,240.0,300.0);
+        let _textarea = self.get_textarea()?;
+
+        match _state {
+            PageState::StoryPicker => {
                 // INPUT TEXT
-                let _= _input_element.set_disabled(false);
-                let _= _input_element.set_placeholder(TEXT_CHAPTER_TEXT_PLACEHOLDER[0]);
+                _input_element.set_disabled(false);
+                _input_element.set_placeholder(TEXT_PICKER_PLACEHOLDER);
+                // TEXTAREA
+                _textarea.set_value("");
+                let _= _textarea.style().set_property("display", "none");
             },
-
-            // Finish
-
-            PageType::Fin => {
-                // Title
-                let _= _context.set_fill_style_str(DEFAULT_COLOR);
-                let _= _context.set_font("36px MyFont");
-                let _= _context.set_text_align("center");
-                let _= _context.fill_text(TITLE, _canvas_width / 2.0, 90.0);
-                let _= _context.set_font("36px MyFont");
-                let _lines: Vec<&str> = TEXT_FIN.split('\n').collect();
-                for i in 0.._lines.len(){
-                    let _= _context.fill_text(_lines[i], _canvas_width / 2.0, (200.0 + (TEXT_SPACE * i) as f32).into());
+            PageState::ApiKeyEntry => {
+                // INPUT TEXT
+                _input_element.set_disabled(false);
+                // a key for another endpoint than the backend's own says where it goes
+                let _judge = self.engine.judge_settings();
+                if _judge.endpoint == _judge.backend.default_endpoint() {
+                    _input_element.set_placeholder(&format!("Enter {} API Key", _judge.backend.name()));
+                } else {
+                    _input_element.set_placeholder(&format!("Enter {} API Key for {}", _judge.backend.name(), _judge.endpoint));
                 }
+            },
+            PageState::Finished => {
                 // TEXTAREA
-                let _= _textarea_message.set_value("");
-                let _= _textarea_cloned.style().set_property("display", "none");
+                _textarea.set_value("");
+                let _= _textarea.style().set_property("display", "none");
             },
-
-            // Contents
-
-            PageType::Input | PageType::Output => {
-
-                // Gray Disaplay 
-                if PageType::Output == _page_type {_context.set_global_alpha(0.3);}
- 
-                // Title
-                let _= _context.set_font("18px Hiragino Sans");
-                let _= _context.set_fill_style_str(DEFAULT_COLOR);
-                let _= _context.set_text_align("center");
-                let _= _context.fill_text("【 LITTLE RED RIDING HOOD 】", _canvas_width / 2.0, 30.0);
-
-                // CHAPTER
-                let _= _context.set_text_align("left");
-                let _lines: Vec<&str> = TEXT_CHAPTER[_chapter].split('\n').collect();
-                for i in 0.._lines.len() {
-                    let _=  _context.fill_text(_lines[i], 10.0, (70.0 + (TEXT_SPACE * i) as f32).into());
+            PageState::ChapterPrompt { chapter } => {
+                // INPUT TEXT, only spoken in pronunciation mode
+                _input_element.set_disabled(self.engine.target().is_some());
+                if let Some(_target) = self.engine.target() {
+                    _input_element.set_placeholder(_target);
+                } else if let Some(_contents) = self.get_story().chapter(chapter) {
+                    _input_element.set_placeholder(&_contents.blank);
                 }
-
-                // Illustration
-                let mut _f  = (0.0, 0.0, 0.0, 0.0);
-                let mut _d = (0.0, 0.0, 0.0, 0.0);
-                match _chapter {
-                    1 => {
-                        _f = (0.0, 300.0, 120.0, 150.0);
-                        _d = (340.0, 440.0, 240.0, 300.0);
-                    }
-                    2 => {
-                        _f = (120.0, 300.0, 120.0, 150.0);
-                        _d = (300.0, 340.0, 240.0, 300.0);
-                    }
-                    3 => {
-                        _f = (0.0, 450.0, 120.0, 150.0);
-                        _d = (300.0, 340.0, 240.0, 300.0);
-                    }
-                    4 => {
-                        _f = (120.0, 450.0, 120.0, 150.0);
-                        _d = (360.0, 480.0, 220.0, 280.0);
-                    }
-                    5 => {
-                        _f = (0.0, 600.0, 120.0, 150.0);
-                        _d = (320.0, 440.0, 240.0, 300.0);
-                    }
-                    6 => {
-                        _f = (120.0, 600.0, 120.0, 150.0);
-                        _d = (330.0, 340.0, 240.0, 300.0);
-                    }
-                    7 => {
-                        _f = (0.0, 750.0, 120.0, 150.0);
-                        _d = (330.0, 380.0, 220.0, 280.0);
-                    }
-                    8 => {
-                        _f = (120.0, 750.0, 120.0, 150.0);
-                        _d = (230.0, 240.0, 120.0, 150.0);
-                    }
-                    _=> {}
-                }
-                let _= _context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                    &self.image, _f.0, _f.1, _f.2, _f.3, _d.0, _d.1, _d.2, _d.3);
-
-                // CONTEXT
-                let _lines: Vec<&str> = TEXT_CHAPTER[_chapter].split('\n').collect();
-                for i in 0.._lines.len() {
-                    let _=  _context.fill_text(_lines[i], 10.0, (70.0 + (TEXT_SPACE * i) as f32).into());
-                }
-
-                // Mike
-                 _context.set_global_alpha(0.5);
-                if self.get_mike_status() {
-                    let _= _context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                    &self.image, 60.0, 900.0, 60.0, 150.0, _canvas_width / 2.0 - 120.0 / 2.0, 210.0, 120.0, 300.0);
-                } else {
-                    _context.set_global_alpha(0.3);
-                    let _= _context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                        &self.image, 0.0, 900.0, 60.0, 150.0, _canvas_width / 2.0 - 120.0 / 2.0, 210.0, 120.0, 300.0);
-                    _context.set_global_alpha(1.0);
-                }
-                
+                // TEXTAREA
+                _textarea.set_value("");
+                let _= _textarea.style().set_property("display", "none");
+            },
+            PageState::AwaitingResponse { .. } | PageState::Feedback { .. } => {
                 // Message from AI
-                if _page_type == PageType::Output {
-                    let _= _input_element.set_value("Touch or Click Screen");
-                    let _= _input_element.set_disabled(true);
-
-                    // border
-                    let _= _context.set_global_alpha(1.0); 
-                    let _= _context.begin_path();
-                    let _= _context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                        &self.image,
-                        125.0, 150.0, 90.0, 50.0, _canvas_left + 10.0, _canvas_top + 40.0, _canvas_width - 20.0, _canvas_height - 100.0);
-
-                    // Message
-                    let _input = _document.get_element_by_id("input").unwrap();
-                    let _message = self.get_message();
-                    let _document = &self.get_document();
-                    let _width = format!("{}px", _canvas_width - 20.0);
-                    let _height = format!("{}px", _canvas_height - 40.0);
-                    let _left = format!("{}px", _canvas_offsetleft as f32 + 10.0);
-                    let _top = format!("{}px", _canvas_top as f32 + 50.0);
-                    let _= _textarea_cloned.style().set_property("display", "block");
-                    let _= _textarea_cloned.style().set_property("left",  &_left);
-                    let _= _textarea_cloned.style().set_property("top",  &_top);
-                    let _= _textarea_cloned.style().set_property("max-width", "860px");
-                    let _= _textarea_cloned.style().set_property("width", &_width);
-                    let _= _textarea_cloned.style().set_property("height", &_height);
-                    let _= _textarea_cloned.style().set_property("visibility", "visible");
-                    let _= _textarea_message.set_value(&_message);
+                if matches!(_state, PageState::Feedback { .. }) {
+                    _input_element.set_value(if self.must_retry() { TEXT_RETRY } else { TEXT_CONTINUE });
+                } else {
+                    _input_element.set_value("");
                 }
-                if _page_type == PageType::Input {
-                    // INPUT TEXT
-                    let _= _input_element.set_disabled(false);
-                    let _= _input_element.set_placeholder(TEXT_CHAPTER_TEXT_PLACEHOLDER[_chapter]);
-                    // TEXTAREA
-                    let _= _textarea_message.set_value("");
-                    let _= _textarea_cloned.style().set_property("display", "none");
+                _input_element.set_disabled(true);
+
+                let _width = format!("{}px", _canvas_width - 20.0);
+                let _height = format!("{}px", _canvas_height - 40.0);
+                let _left = format!("{}px", _canvas_offsetleft as f32 + 10.0);
+                let _top = format!("{}px", _canvas_top as f32 + 50.0);
+                let _= _textarea.style().set_property("display", "block");
+                let _= _textarea.style().set_property("left",  &_left);
+                let _= _textarea.style().set_property("top",  &_top);
+                let _= _textarea.style().set_property("max-width", "860px");
+                let _= _textarea.style().set_property("width", &_width);
+                let _= _textarea.style().set_property("height", &_height);
+                let _= _textarea.style().set_property("visibility", "visible");
+                let _color = match self.get_grade() {
+                    Some(_grade) if !_grade.correct => "#ffb3b3",
+                    _ => "#d9ffb3",
+                };
+                let _= _textarea.style().set_property("color", _color);
+                _textarea.set_value(&self.engine.display_message());
+                // pronunciation is drawn word by word on the canvas
+                if self.engine.pronunciation().is_some() {
+                    let _= _textarea.style().set_property("display", "none");
                 }
             }
         }
-     }
+        Ok(())
+    }
 
-    // clear screen
+    fn save(&self) {
+        save_snapshot(&self.engine.snapshot());
+    }
 
-    fn clear(&self){
-        let _context = self.get_context();
-         _context.clear_rect(
-            0.0,
-            0.0,
-            1000.0 as f64,
-            1000.0 as f64,
-        );
+    // the API key typed on the key page is kept out of the log
+
+    fn record(&mut self, event: &GameEvent, now: f64) {
+        if !event.is_recorded() { return; }
+        let _event = match (self.engine.state(), event) {
+            (PageState::ApiKeyEntry, GameEvent::Enter { .. }) => GameEvent::Enter { text: String::from(REDACTED) },
+            _ => event.clone(),
+        };
+        if self.events.as_ref().is_some_and(|e| e.is_full()) {
+            self.start_recording();
+        }
+        if let Some(_events) = self.events.as_mut() {
+            _events.push(now, _event);
+            save_event_log(_events);
+        }
     }
-}
\ No newline at end of file
+
+    // effects on the page; false for the ones left to the game loop
+
+    fn apply(&mut self, effect: &Effect) -> Result<bool> {
+        match effect {
+            Effect::ClearInput => {
+                let _input = self.get_input()?;
+                _input.set_value("");
+                _input.set_class_name("");
+            },
+            Effect::SetInput(text) => {
+                let _input = self.get_input()?;
+                _input.set_value(text);
+                _input.set_class_name("");
+                _input.focus()?;
+            },
+            Effect::SetTentativeInput(text) => {
+                let _input = self.get_input()?;
+                _input.set_value(text);
+                _input.set_class_name("tentative");
+            },
+            Effect::SaveApiKey(api_key) => save_api_key(api_key),
+            Effect::ForgetApiKey => forget_api_key(),
+            Effect::StartRecognition | Effect::StopRecognition | Effect::ConfigureSpeech(_) | Effect::Judge { .. } | Effect::CancelJudge => return Ok(false),
+            Effect::Speak { .. } | Effect::CancelSpeech => return Ok(false),
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,252 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, AbortController, AbortSignal, Headers, ReadableStreamDefaultReader, Request, RequestInit, Response};
+use crate::judge::{Grade, JudgeRequest, TranslationJudge};
+use crate::error::{ApiError, HttpError};
+use crate::settings::Settings;
+use crate::sse::SseParser;
+
+macro_rules! log {
+    ( $( $t:tt )* ) => {
+        web_sys::console::log_1(&format!( $( $t )* ).into())
+    }
+}
+
+// fetch based HTTP client; every request has its own promise and optional AbortSignal
+
+impl From<JsValue> for HttpError {
+    fn from(e: JsValue) -> Self {
+        let _name = js_sys::Reflect::get(&e, &JsValue::from_str("name")).ok().and_then(|n| n.as_string());
+        if _name.as_deref() == Some("AbortError") {
+            return HttpError::Aborted;
+        }
+        HttpError::Network(e.as_string().or(_name).unwrap_or_else(|| format!("{:?}", e)))
+    }
+}
+
+async fn fetch(request: &Request) -> Result<(Response, String), HttpError> {
+    let _window = window().ok_or_else(|| HttpError::Network(String::from("no window")))?;
+    let _response: Response = JsFuture::from(_window.fetch_with_request(request)).await?.dyn_into()?;
+    let _text = JsFuture::from(_response.text()?).await?.as_string().unwrap_or_default();
+    Ok((_response, _text))
+}
+
+// GET a text resource served with the game
+
+pub async fn get_text(url: &str) -> Result<String, HttpError> {
+    let _request = Request::new_with_str(url)?;
+    let (_response, _text) = fetch(&_request).await?;
+    if !_response.ok() {
+        return Err(HttpError::Api(ApiError::from_response(_response.status(), &_text)));
+    }
+    Ok(_text)
+}
+
+// one attempt's signal: aborted by the caller's signal or after timeout_ms
+
+struct Deadline {
+    controller: AbortController,
+    outer: Option<AbortSignal>,
+    timed_out: Rc<Cell<bool>>,
+    handle: Option<i32>,
+    _on_timeout: Closure<dyn FnMut()>,
+    _on_abort: Closure<dyn FnMut()>,
+}
+
+impl Deadline {
+    fn new(timeout_ms: u32, outer: Option<&AbortSignal>) -> Result<Self, HttpError> {
+        let _window = window().ok_or_else(|| HttpError::Network(String::from("no window")))?;
+        let _controller = AbortController::new()?;
+        let _timed_out = Rc::new(Cell::new(false));
+
+        let _on_timeout = {
+            let _controller = _controller.clone();
+            let _timed_out = Rc::clone(&_timed_out);
+            Closure::wrap(Box::new(move || {
+                _timed_out.set(true);
+                _controller.abort();
+            }) as Box<dyn FnMut()>)
+        };
+        let _on_abort = {
+            let _controller = _controller.clone();
+            Closure::wrap(Box::new(move || _controller.abort()) as Box<dyn FnMut()>)
+        };
+        if let Some(_outer) = outer {
+            if _outer.aborted() {
+                return Err(HttpError::Aborted);
+            }
+            _outer.set_onabort(Some(_on_abort.as_ref().unchecked_ref()));
+        }
+        let _handle = match timeout_ms {
+            0 => None,
+            _ => Some(_window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                _on_timeout.as_ref().unchecked_ref(), timeout_ms.min(i32::MAX as u32) as i32)?),
+        };
+        Ok(Deadline {
+            controller: _controller,
+            outer: outer.cloned(),
+            timed_out: _timed_out,
+            handle: _handle,
+            _on_timeout,
+            _on_abort,
+        })
+    }
+
+    fn signal(&self) -> AbortSignal {
+        self.controller.signal()
+    }
+
+    // an abort caused by the timer is a timeout, not a cancel
+
+    fn error(&self, e: HttpError) -> HttpError {
+        match e {
+            HttpError::Aborted if self.timed_out.get() => HttpError::Timeout,
+            e => e,
+        }
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        if let (Some(_window), Some(_handle)) = (window(), self.handle) {
+            _window.clear_timeout_with_handle(_handle);
+        }
+        if let Some(_outer) = &self.outer {
+            _outer.set_onabort(None);
+        }
+    }
+}
+
+// resolves after millis, or as soon as the signal is aborted
+
+async fn sleep(millis: u32, signal: Option<&AbortSignal>) {
+    if signal.is_some_and(|s| s.aborted()) { return; }
+    let _promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        if let Some(_window) = window() {
+            let _ = _window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis.min(i32::MAX as u32) as i32);
+        }
+        if let Some(_signal) = signal {
+            let _ = _signal.add_event_listener_with_callback("abort", &resolve);
+        }
+    });
+    let _ = JsFuture::from(_promise).await;
+}
+
+// wait before retry number `retry`; false when the caller cancelled meanwhile
+
+async fn backoff(settings: &Settings, retry: u32, signal: Option<&AbortSignal>) -> bool {
+    let _delay = settings.backoff(retry);
+    log!("Retry {} of {} in {} ms", retry, settings.max_retries, _delay);
+    sleep(_delay, signal).await;
+    !signal.is_some_and(|s| s.aborted())
+}
+
+fn post_request(request: &JudgeRequest, signal: Option<&AbortSignal>) -> Result<Request, HttpError> {
+    let _headers = Headers::new()?;
+    for (name, value) in request.headers.iter() {
+        _headers.set(name, value)?;
+    }
+    let _init = RequestInit::new();
+    _init.set_method("POST");
+    _init.set_headers(&_headers);
+    _init.set_body(&JsValue::from_str(&request.body));
+    _init.set_signal(signal);
+    Ok(Request::new_with_str_and_init(&request.url, &_init)?)
+}
+
+// POST a judge request, body of a 2xx response
+
+pub async fn send(request: &JudgeRequest, signal: Option<&AbortSignal>) -> Result<String, HttpError> {
+    let _request = post_request(request, signal)?;
+    let (_response, _text) = fetch(&_request).await?;
+    if !_response.ok() {
+        return Err(HttpError::Api(ApiError::from_response(_response.status(), &_text)));
+    }
+    Ok(_text)
+}
+
+// send with a timeout per attempt, retrying 429 / 5xx / network errors with exponential backoff
+
+pub async fn judge(judge: &dyn TranslationJudge, prompt: &str, signal: Option<&AbortSignal>, settings: &Settings) -> Result<Grade, HttpError> {
+    let _request = judge.request(prompt).map_err(|e| HttpError::Parse(format!("Failed to serialize request body: {}", e)))?;
+    let mut _retry = 0;
+    let _body = loop {
+        let _deadline = Deadline::new(settings.timeout_ms, signal)?;
+        match send(&_request, Some(&_deadline.signal())).await.map_err(|e| _deadline.error(e)) {
+            Err(e) if e.retryable() && _retry < settings.max_retries => {
+                log!("{}", e);
+                drop(_deadline);
+                _retry += 1;
+                if !backoff(settings, _retry, signal).await {
+                    return Err(HttpError::Aborted);
+                }
+            },
+            _result => break _result?,
+        }
+    };
+    judge.parse_response(&_body).map_err(HttpError::Parse)
+}
+
+// POST a server-sent events request, calling on_data with each event's data as it arrives
+
+pub async fn stream(request: &JudgeRequest, signal: Option<&AbortSignal>, mut on_data: impl FnMut(&str)) -> Result<(), HttpError> {
+    let _window = window().ok_or_else(|| HttpError::Network(String::from("no window")))?;
+    let _request = post_request(request, signal)?;
+    let _response: Response = JsFuture::from(_window.fetch_with_request(&_request)).await?.dyn_into()?;
+    if !_response.ok() {
+        let _text = JsFuture::from(_response.text()?).await?.as_string().unwrap_or_default();
+        return Err(HttpError::Api(ApiError::from_response(_response.status(), &_text)));
+    }
+    let _body = _response.body().ok_or_else(|| HttpError::Network(String::from("Empty response")))?;
+    let _reader: ReadableStreamDefaultReader = _body.get_reader().unchecked_into();
+    let mut _parser = SseParser::new();
+    loop {
+        let _chunk = JsFuture::from(_reader.read()).await?;
+        let _done = js_sys::Reflect::get(&_chunk, &JsValue::from_str("done"))?.as_bool().unwrap_or(true);
+        if _done { break; }
+        let _value: js_sys::Uint8Array = js_sys::Reflect::get(&_chunk, &JsValue::from_str("value"))?.dyn_into()?;
+        for _data in _parser.push(&_value.to_vec()) {
+            on_data(&_data);
+        }
+    }
+    if let Some(_data) = _parser.finish() {
+        on_data(&_data);
+    }
+    Ok(())
+}
+
+// streamed judge: on_text gets the text as it arrives, the grade is parsed at the end.
+// only retried while nothing has been shown yet
+
+pub async fn judge_stream(judge: &dyn TranslationJudge, prompt: &str, signal: Option<&AbortSignal>, settings: &Settings, mut on_text: impl FnMut(&str)) -> Result<Grade, HttpError> {
+    let _request = match judge.stream_request(prompt) {
+        Some(request) => request.map_err(|e| HttpError::Parse(format!("Failed to serialize request body: {}", e)))?,
+        None => return self::judge(judge, prompt, signal, settings).await,
+    };
+    let mut _text = String::new();
+    let mut _retry = 0;
+    loop {
+        let _deadline = Deadline::new(settings.timeout_ms, signal)?;
+        let _result = stream(&_request, Some(&_deadline.signal()), |data| {
+            // chunks without text (e.g. the final finishReason) are skipped
+            if let Ok(_chunk) = judge.parse_stream_chunk(data) {
+                _text.push_str(&_chunk);
+                on_text(&_chunk);
+            }
+        }).await.map_err(|e| _deadline.error(e));
+        match _result {
+            Err(e) if e.retryable() && _text.is_empty() && _retry < settings.max_retries => {
+                log!("{}", e);
+                drop(_deadline);
+                _retry += 1;
+                if !backoff(settings, _retry, signal).await {
+                    return Err(HttpError::Aborted);
+                }
+            },
+            _result => break _result?,
+        }
+    }
+    Grade::from_text(&_text).map_err(HttpError::Parse)
+}
@@ -0,0 +1,459 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+
+// LLM backends that judge the player's translation
+
+pub const GEMINI_API_ENDPOINT: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+pub const GEMINI_MODEL: &str = "gemini-2.0-flash-exp";
+pub const OPENAI_API_ENDPOINT: &str = "https://api.openai.com/v1/chat/completions";
+pub const OPENAI_MODEL: &str = "gpt-4o-mini";
+pub const OLLAMA_API_ENDPOINT: &str = "http://localhost:11434/api/chat";
+pub const OLLAMA_MODEL: &str = "llama3.2";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Backend {
+    Gemini,
+    OpenAi,     // OpenAI compatible chat completions (also llama.cpp server, LM Studio, ...)
+    Ollama,
+    Offline,    // built-in checker against the story's reference answers, no HTTP
+}
+
+impl Backend {
+    pub fn parse(name: &str) -> Option<Backend> {
+        match name.to_ascii_lowercase().as_str() {
+            "gemini" => Some(Backend::Gemini),
+            "openai" => Some(Backend::OpenAi),
+            "ollama" => Some(Backend::Ollama),
+            "offline" => Some(Backend::Offline),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Gemini => "Gemini",
+            Backend::OpenAi => "OpenAI",
+            Backend::Ollama => "Ollama",
+            Backend::Offline => "Offline",
+        }
+    }
+    pub fn default_endpoint(&self) -> &'static str {
+        match self {
+            Backend::Gemini => GEMINI_API_ENDPOINT,
+            Backend::OpenAi => OPENAI_API_ENDPOINT,
+            Backend::Ollama => OLLAMA_API_ENDPOINT,
+            Backend::Offline => "",
+        }
+    }
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            Backend::Gemini => GEMINI_MODEL,
+            Backend::OpenAi => OPENAI_MODEL,
+            Backend::Ollama => OLLAMA_MODEL,
+            Backend::Offline => "",
+        }
+    }
+    pub fn needs_key(&self) -> bool {
+        !matches!(self, Backend::Ollama | Backend::Offline)
+    }
+}
+
+// Backend selection, independent of the API key
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JudgeSettings {
+    pub backend: Backend,
+    pub endpoint: String,
+    pub model: String,
+}
+
+impl JudgeSettings {
+    pub fn new(backend: Backend) -> Self {
+        JudgeSettings {
+            backend,
+            endpoint: backend.default_endpoint().to_string(),
+            model: backend.default_model().to_string(),
+        }
+    }
+
+    // None for the offline backend, which answers without HTTP
+
+    pub fn create_judge(&self, key: &str) -> Option<Box<dyn TranslationJudge>> {
+        match self.backend {
+            Backend::Gemini => Some(Box::new(GeminiJudge { endpoint: self.endpoint.clone(), model: self.model.clone(), key: key.to_string() })),
+            Backend::OpenAi => Some(Box::new(OpenAiJudge { endpoint: self.endpoint.clone(), model: self.model.clone(), key: key.to_string() })),
+            Backend::Ollama => Some(Box::new(OllamaJudge { endpoint: self.endpoint.clone(), model: self.model.clone() })),
+            Backend::Offline => None,
+        }
+    }
+}
+
+impl Default for JudgeSettings {
+    fn default() -> Self {
+        JudgeSettings::new(Backend::Gemini)
+    }
+}
+
+// API key with the backend and endpoint it was entered for; never sent anywhere else,
+// so a link with another ?endpoint= cannot pick up the saved key
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub key: String,
+    pub backend: Backend,
+    pub endpoint: String,
+}
+
+impl ApiKey {
+    pub fn new(key: &str, judge: &JudgeSettings) -> Self {
+        ApiKey { key: key.to_string(), backend: judge.backend, endpoint: judge.endpoint.clone() }
+    }
+    pub fn is_for(&self, judge: &JudgeSettings) -> bool {
+        self.backend == judge.backend && self.endpoint == judge.endpoint
+    }
+}
+
+// Structured grade requested from the backend
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Grade {
+    pub correct: bool,
+    #[serde(deserialize_with = "score_from_number")]
+    pub score: u8,              // 0 - 100
+    pub corrected_sentence: String,
+    pub explanation: String,
+    #[serde(default)]
+    pub missing: Vec<String>,   // words of the answer the player left out (offline checker)
+}
+
+fn score_from_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    let _score = f64::deserialize(deserializer)?;
+    Ok(_score.round().clamp(0.0, 100.0) as u8)
+}
+
+impl Grade {
+
+    // model text may wrap the JSON in a ```json fence
+
+    pub fn from_text(text: &str) -> Result<Grade, String> {
+        let _json = text.trim()
+            .trim_start_matches("```json")
+            .trim_start_matches("```")
+            .trim_end_matches("```")
+            .trim();
+        serde_json::from_str::<Grade>(_json).map_err(|e| format!("Error parsing grade: {}", e))
+    }
+
+    pub fn schema() -> serde_json::Value {
+        json!({
+            "type": "OBJECT",
+            "properties": {
+                "correct": { "type": "BOOLEAN" },
+                "score": { "type": "INTEGER" },
+                "corrected_sentence": { "type": "STRING" },
+                "explanation": { "type": "STRING" }
+            },
+            "required": ["correct", "score", "corrected_sentence", "explanation"]
+        })
+    }
+
+    // JSON Schema spelling for OpenAI compatible / Ollama backends
+
+    pub fn json_schema() -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "correct": { "type": "boolean" },
+                "score": { "type": "integer" },
+                "corrected_sentence": { "type": "string" },
+                "explanation": { "type": "string" }
+            },
+            "required": ["correct", "score", "corrected_sentence", "explanation"]
+        })
+    }
+
+    // without reveal (retry mode, more tries to come) the answer and its missing words are kept back
+
+    pub fn render(&self, reveal: bool) -> String {
+        let _verdict = if self.correct { "Correct!" } else { "Not quite." };
+        if !reveal {
+            return format!("{}\nScore: {} / 100\n\n{}", _verdict, self.score, self.explanation);
+        }
+        let mut _text = format!("{}\nScore: {} / 100\nModel answer: {}\n\n{}", _verdict, self.score, self.corrected_sentence, self.explanation);
+        if !self.missing.is_empty() {
+            _text.push_str(&format!("\nMissing words: {}", self.missing.join(", ")));
+        }
+        _text
+    }
+
+    // explanation typed so far in a grade still being streamed: the JSON string
+    // after "explanation": up to its closing quote or the end of the text
+
+    pub fn partial_explanation(text: &str) -> Option<String> {
+        let _start = text.find("\"explanation\"")? + "\"explanation\"".len();
+        let _rest = text[_start..].trim_start().strip_prefix(':')?.trim_start().strip_prefix('"')?;
+        let mut _explanation = String::new();
+        let mut _chars = _rest.chars();
+        while let Some(c) = _chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match _chars.next() {
+                    Some('n') => _explanation.push('\n'),
+                    Some('t') => _explanation.push('\t'),
+                    Some('u') => {
+                        let _hex: String = _chars.by_ref().take(4).collect();
+                        // cut in the middle: wait for the next chunk
+                        if _hex.len() < 4 { break; }
+                        if let Some(_char) = u32::from_str_radix(&_hex, 16).ok().and_then(char::from_u32) {
+                            _explanation.push(_char);
+                        }
+                    },
+                    Some(_escaped) => _explanation.push(_escaped),     // \" \\ \/
+                    None => break,
+                },
+                _ => _explanation.push(c),
+            }
+        }
+        Some(_explanation)
+    }
+}
+
+// the judge answer the engine and event tests are played with
+
+#[cfg(test)]
+impl Grade {
+    pub fn sample(score: u8) -> Grade {
+        Grade {
+            correct: score >= crate::settings::PASS_SCORE,
+            score,
+            corrected_sentence: String::from("Don't forget to say good morning"),
+            explanation: String::from("解説"),
+            missing: Vec::new(),
+        }
+    }
+}
+
+// HTTP request to send for a prompt
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JudgeRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+pub trait TranslationJudge {
+    fn request(&self, prompt: &str) -> Result<JudgeRequest, serde_json::Error>;
+    fn parse_text(&self, body: &str) -> Result<String, String>;
+
+    fn parse_response(&self, body: &str) -> Result<Grade, String> {
+        Grade::from_text(&self.parse_text(body)?)
+    }
+
+    // server-sent events request, None when the backend does not stream
+
+    fn stream_request(&self, _prompt: &str) -> Option<Result<JudgeRequest, serde_json::Error>> {
+        None
+    }
+
+    // text of one event's data
+
+    fn parse_stream_chunk(&self, data: &str) -> Result<String, String> {
+        self.parse_text(data)
+    }
+}
+
+// Type Definition Of Gemini API Payload
+
+#[derive(Serialize)]
+pub struct GeminiRequestPart {
+    pub text: String,
+}
+#[derive(Serialize)]
+pub struct GeminiRequestContent {
+    pub parts: Vec<GeminiRequestPart>,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGenerationConfig {
+    pub response_mime_type: String,
+    pub response_schema: serde_json::Value,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiRequestBody {
+    pub contents: Vec<GeminiRequestContent>,
+    pub generation_config: GeminiGenerationConfig,
+}
+#[derive(Deserialize, Debug)]
+pub struct GeminiResponseCandidatePart {
+    pub text: String,
+}
+#[derive(Deserialize, Debug)]
+pub struct GeminiResponseCandidateContent {
+    pub parts: Vec<GeminiResponseCandidatePart>,
+}
+#[derive(Deserialize, Debug)]
+pub struct GeminiResponseCandidate {
+    pub content: GeminiResponseCandidateContent,
+}
+#[derive(Deserialize, Debug)]
+pub struct GeminiResponseBody {
+    pub candidates: Vec<GeminiResponseCandidate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeminiJudge {
+    pub endpoint: String,
+    pub model: String,
+    pub key: String,
+}
+
+impl GeminiJudge {
+    fn body(&self, prompt: &str) -> Result<String, serde_json::Error> {
+        let request_body = GeminiRequestBody {
+            contents: vec![GeminiRequestContent {
+                parts: vec![GeminiRequestPart { text: prompt.to_string() }],
+            }],
+            generation_config: GeminiGenerationConfig {
+                response_mime_type: String::from("application/json"),
+                response_schema: Grade::schema(),
+            },
+        };
+        serde_json::to_string(&request_body)
+    }
+
+    // the key goes in a header, never in the URL (logs, history)
+
+    fn headers(&self) -> Vec<(String, String)> {
+        vec![
+            (String::from("Content-Type"), String::from("application/json")),
+            (String::from("x-goog-api-key"), self.key.clone()),
+        ]
+    }
+}
+
+impl TranslationJudge for GeminiJudge {
+    fn request(&self, prompt: &str) -> Result<JudgeRequest, serde_json::Error> {
+        Ok(JudgeRequest {
+            url: format!("{}/{}:generateContent", self.endpoint.trim_end_matches('/'), self.model),
+            headers: self.headers(),
+            body: self.body(prompt)?,
+        })
+    }
+
+    fn stream_request(&self, prompt: &str) -> Option<Result<JudgeRequest, serde_json::Error>> {
+        Some(self.body(prompt).map(|body| JudgeRequest {
+            url: format!("{}/{}:streamGenerateContent?alt=sse", self.endpoint.trim_end_matches('/'), self.model),
+            headers: self.headers(),
+            body,
+        }))
+    }
+
+    fn parse_text(&self, body: &str) -> Result<String, String> {
+        let parsed_response = serde_json::from_str::<GeminiResponseBody>(body)
+            .map_err(|e| format!("Error parsing response: {}", e))?;
+        parsed_response.candidates.first()
+            .and_then(|candidate| candidate.content.parts.first())
+            .map(|part| part.text.clone())
+            .ok_or_else(|| String::from("Error: No candidates found"))
+    }
+}
+
+// Type Definition Of OpenAI / Ollama Chat Payload
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+#[derive(Serialize)]
+pub struct ChatRequestBody {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,  // OpenAI compatible
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<serde_json::Value>,           // Ollama
+}
+#[derive(Deserialize, Debug)]
+pub struct OpenAiResponseChoice {
+    pub message: ChatMessage,
+}
+#[derive(Deserialize, Debug)]
+pub struct OpenAiResponseBody {
+    pub choices: Vec<OpenAiResponseChoice>,
+}
+#[derive(Deserialize, Debug)]
+pub struct OllamaResponseBody {
+    pub message: ChatMessage,
+}
+
+fn chat_body(model: &str, prompt: &str) -> ChatRequestBody {
+    ChatRequestBody {
+        model: model.to_string(),
+        messages: vec![ChatMessage { role: String::from("user"), content: prompt.to_string() }],
+        stream: None,
+        response_format: None,
+        format: None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenAiJudge {
+    pub endpoint: String,
+    pub model: String,
+    pub key: String,
+}
+
+impl TranslationJudge for OpenAiJudge {
+    fn request(&self, prompt: &str) -> Result<JudgeRequest, serde_json::Error> {
+        let mut headers = vec![(String::from("Content-Type"), String::from("application/json"))];
+        if !self.key.is_empty() {
+            headers.push((String::from("Authorization"), format!("Bearer {}", self.key)));
+        }
+        Ok(JudgeRequest {
+            url: self.endpoint.clone(),
+            headers,
+            body: serde_json::to_string(&ChatRequestBody {
+                response_format: Some(json!({ "type": "json_object" })),
+                ..chat_body(&self.model, prompt)
+            })?,
+        })
+    }
+
+    fn parse_text(&self, body: &str) -> Result<String, String> {
+        let parsed_response = serde_json::from_str::<OpenAiResponseBody>(body)
+            .map_err(|e| format!("Error parsing response: {}", e))?;
+        parsed_response.choices.first()
+            .map(|choice| choice.message.content.clone())
+            .ok_or_else(|| String::from("Error: No choices found"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OllamaJudge {
+    pub endpoint: String,
+    pub model: String,
+}
+
+impl TranslationJudge for OllamaJudge {
+    fn request(&self, prompt: &str) -> Result<JudgeRequest, serde_json::Error> {
+        Ok(JudgeRequest {
+            url: self.endpoint.clone(),
+            headers: vec![(String::from("Content-Type"), String::from("application/json"))],
+            body: serde_json::to_string(&ChatRequestBody {
+                stream: Some(false),
+                format: Some(Grade::json_schema()),
+                ..chat_body(&self.model, prompt)
+            })?,
+        })
+    }
+
+    fn parse_text(&self, body: &str) -> Result<String, String> {
+        serde_json::from_str::<OllamaResponseBody>(body)
+            .map(|parsed_response| parsed_response.message.content)
+            .map_err(|e| format!("Error parsing response: {}", e))
+    }
+}
@@ -1,14 +1,33 @@
 mod game;
 mod common;
+mod story;
+mod judge;
+mod checker;
+mod session;
+mod settings;
+mod storage;
+mod http;
+mod sse;
+mod error;
+mod engine;
+mod render;
+mod event;
+mod narration;
+mod pronunciation;
 use crate::common::*;
-use anyhow::Result;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::JsValue;
 use wasm_bindgen::prelude::*;
-use web_sys::{HtmlImageElement, window, XmlHttpRequest, Event, EventTarget, HtmlInputElement, MouseEvent, KeyboardEvent};
+use web_sys::{AbortController, HtmlImageElement, window, MouseEvent, KeyboardEvent, UrlSearchParams, SpeechSynthesisUtterance, SpeechSynthesisVoice};
 use std::{cell::RefCell, rc::Rc};
 use game::Game;
 use game::StaticGame;
+use story::*;
+use judge::*;
+use settings::*;
+use storage::*;
+use error::{GameError, HttpError};
+use event::{Alternative, Command, Effect, GameEvent};
 
 macro_rules! log {
     ( $( $t:tt )* ) => {
@@ -22,9 +41,15 @@ macro_rules! log {
 pub fn main() -> Result<(), JsValue>{
     console_error_panic_hook::set_once();
 
+    let document = window().and_then(|w| w.document()).ok_or_else(|| JsValue::from_str("no document"))?;
+    let game = match Game::new(document) {
+        Ok(game) => game,
+        Err(e) => {
+            log!("{}", e);
+            return Err(JsValue::from_str(&e.to_string()));
+        }
+    };
     wasm_bindgen_futures::spawn_local(async move{
-        let document = window().unwrap().document().unwrap();
-        let game = Game::new(document);
         GameLoop::start(game)
             .await;
     });
@@ -36,11 +61,8 @@ extern "C" {
     #[wasm_bindgen(js_name = "webkitSpeechRecognition")]
     pub type WebkitSpeechRecognition;
 
-    #[wasm_bindgen(constructor, js_class = "webkitSpeechRecognition")]
-    pub fn new() -> WebkitSpeechRecognition;
-
-    #[wasm_bindgen(method, js_name = "start")]
-    pub fn start(this: &WebkitSpeechRecognition);
+    #[wasm_bindgen(method, catch, js_name = "start")]
+    pub fn start(this: &WebkitSpeechRecognition) -> Result<(), JsValue>;   // InvalidStateError when already started
 
     #[wasm_bindgen(method, js_name = "stop")]
     pub fn stop(this: &WebkitSpeechRecognition);
@@ -60,6 +82,9 @@ extern "C" {
 
     #[wasm_bindgen(method, setter, js_name = "continuous")]
     pub fn set_continuous(this: &WebkitSpeechRecognition, value: bool);
+
+    #[wasm_bindgen(method, setter, js_name = "maxAlternatives")]
+    pub fn set_max_alternatives(this: &WebkitSpeechRecognition, value: u32);
 }
 
 struct GameLoop;
@@ -70,16 +95,23 @@ impl GameLoop {
 
         let ref_game = Rc::new(RefCell::new(game));
 
-        // callback WebkitSpeechRecognition from JS
 
-        let ref_game_cloned_speech = Rc::clone(&ref_game);
-        let recognition = WebkitSpeechRecognition::new();
-        let ref_recognition = Rc::new(RefCell::new(recognition));
-        let ref_recognition_cloned = Rc::clone(&ref_recognition);
+        // callback WebkitSpeechRecognition from JS; keyboard only when the browser has none
+
+        let recognition = new_recognition();
+        if recognition.is_none() {
+            log!("Speech recognition is not available: keyboard only");
+        }
+        ref_game.borrow_mut().set_speech_available(recognition.is_some());
+        let dispatcher = Dispatcher {
+            game: Rc::clone(&ref_game),
+            recognition: Rc::new(recognition),
+            pending: Rc::new(RefCell::new(None)),
+        };
+        let dispatcher_speech = dispatcher.clone();
 
-        ref_recognition.borrow_mut().set_lang("en-US"); // en-US, ja-JP
-        ref_recognition.borrow_mut().set_interim_results(true); // true: Get intermediate results
-        ref_recognition.borrow_mut().set_continuous(false); // true: Continue if speeach is interrupted
+        // defaults until the settings are loaded
+        dispatcher.configure_speech(&SpeechSettings::default());
 
         // speach recognition onresult
 
@@ -112,46 +144,64 @@ impl GameLoop {
                         }
                 };
 
-                // alternative: SpeechRecognitionAlternative
-                let alternative = match js_sys::Reflect::get_u32(&result_item, 0) {
-                    Ok(alt) => alt,
-                    Err(_) => {
-                        log!("Failed to get alternative from result item at index {}", last_result_item_index);
-                        return;
-                    }
-                };
-
-                let transcript = js_sys::Reflect::get(&alternative, &JsValue::from_str("transcript"))
-                    .ok().and_then(|v| v.as_string()).unwrap_or_default();
-                let confidence = js_sys::Reflect::get(&alternative, &JsValue::from_str("confidence"))
-                    .ok().and_then(|v| v.as_f64()).unwrap_or(0.0);
-                   
                 let is_final = js_sys::Reflect::get(&result_item, &JsValue::from_str("isFinal"))
                     .ok().and_then(|v| v.as_bool()).unwrap_or(false); // true: fainal result
 
-                //log!("Transcript (isFinal: {}): \"{}\", Confidence: {:.2}", is_final, transcript, confidence);
+                // continuous mode: every final result of the session, in order
+                let mut _finals = Vec::new();
+                for i in 0..last_result_item_index {
+                    let _item = js_sys::Reflect::get_u32(&results, i).unwrap_or(JsValue::UNDEFINED);
+                    if js_sys::Reflect::get(&_item, &JsValue::from_str("isFinal")).ok().and_then(|v| v.as_bool()) != Some(true) { continue; }
+                    let _alternative = js_sys::Reflect::get_u32(&_item, 0).unwrap_or(JsValue::UNDEFINED);
+                    if let Some(_text) = js_sys::Reflect::get(&_alternative, &JsValue::from_str("transcript")).ok().and_then(|v| v.as_string()) {
+                        _finals.push(_text.trim().to_string());
+                    }
+                }
 
+                // alternatives: SpeechRecognitionAlternative, up to maxAlternatives, best first
+                let alternatives_length = js_sys::Reflect::get(&result_item, &JsValue::from_str("length"))
+                    .ok().and_then(|l| l.as_f64()).unwrap_or(0.0) as u32;
+                let mut alternatives = Vec::new();
+                for i in 0..alternatives_length {
+                    let alternative = js_sys::Reflect::get_u32(&result_item, i).unwrap_or(JsValue::UNDEFINED);
+                    let transcript = js_sys::Reflect::get(&alternative, &JsValue::from_str("transcript"))
+                        .ok().and_then(|v| v.as_string()).unwrap_or_default();
+                    let confidence = js_sys::Reflect::get(&alternative, &JsValue::from_str("confidence"))
+                        .ok().and_then(|v| v.as_f64()).unwrap_or(0.0); // 0.0 - 1.0, often 0 for interim results
+                    let mut _text = _finals.clone();
+                    _text.push(transcript.trim().to_string());
+                    alternatives.push(Alternative { text: _text.join(" "), confidence });
+                }
+                if alternatives.is_empty() {
+                    log!("Failed to get alternative from result item at index {}", last_result_item_index);
+                    return;
+                }
+                let Alternative { text, confidence } = alternatives.remove(0);
+
+                // change input_text context, tentatively until the result is final
                 if is_final {
-                    // change input_text context
-                    ref_game_cloned_speech.borrow_mut().on_input_changed(&transcript);
+                    dispatcher_speech.dispatch(GameEvent::Transcript { text, confidence, alternatives });
+                } else {
+                    dispatcher_speech.dispatch(GameEvent::InterimTranscript { text, confidence });
                 }
             }
         }) as Box<dyn FnMut(JsValue)>);
 
-        ref_recognition.borrow_mut().add_event_listener("result", &on_result);
+        dispatcher.listen("result", &on_result);
         on_result.forget();
 
         // speech recognition onerror
 
+        let dispatcher_speech_error = dispatcher.clone();
         let on_error = Closure::wrap(Box::new(move |error_event: JsValue| {
             let error_type = js_sys::Reflect::get(&error_event, &JsValue::from_str("error"))
                 .unwrap_or_else(|_| JsValue::from_str("unknown error"))
                 .as_string()
                 .unwrap_or_else(|| "unknown error".to_string());
-            log!("Speech recognition error: {}", error_type);
+            dispatcher_speech_error.dispatch(GameEvent::SpeechError { error: error_type });
         }) as Box<dyn FnMut(JsValue)>);
 
-        ref_recognition.borrow_mut().add_event_listener("error", &on_error);
+        dispatcher.listen("error", &on_error);
         on_error.forget();
 
         // speech recognition onstart
@@ -159,17 +209,25 @@ impl GameLoop {
         let on_start = Closure::wrap(Box::new(move |_: JsValue| {
             log!("Speech recognition service has started.");
         }) as Box<dyn FnMut(JsValue)>);
-        ref_recognition.borrow_mut().add_event_listener("start", &on_start);
+        dispatcher.listen("start", &on_start);
         on_start.forget();
 
+        // speech recognition onspeechend: the player stopped speaking
+
+        let dispatcher_processing = dispatcher.clone();
+        let on_speech_end = Closure::wrap(Box::new(move |_: JsValue| {
+            dispatcher_processing.dispatch(GameEvent::SpeechProcessing);
+        }) as Box<dyn FnMut(JsValue)>);
+        dispatcher.listen("speechend", &on_speech_end);
+        on_speech_end.forget();
+
         // speech recognition onend
 
-        let ref_game_cloned_mike = Rc::clone(&ref_game);
+        let dispatcher_mike = dispatcher.clone();
         let on_end = Closure::wrap(Box::new(move |_: JsValue| {
-            ref_game_cloned_mike.borrow_mut().set_mike_off();
-            ref_recognition.borrow_mut().stop();
+            dispatcher_mike.dispatch(GameEvent::SpeechEnd);
         }) as Box<dyn FnMut(JsValue)>);
-        ref_recognition_cloned.borrow_mut().add_event_listener("end", &on_end);
+        dispatcher.listen("end", &on_end);
         on_end.forget();
 
         // callback frame from JS
@@ -177,13 +235,13 @@ impl GameLoop {
         {
             let closure = Rc::new(RefCell::new(None));
             let closure_cloned = Rc::clone(&closure);
-            let ref_game_frame_cloned = Rc::clone(&ref_game);
+            let dispatcher_frame = dispatcher.clone();
             let mut frame = 0;
 
             closure_cloned.replace(Some(Closure::wrap(Box::new(move |_time: f64| {
                 frame += 1;
                 if frame % 5 == 0 {
-                    ref_game_frame_cloned.borrow_mut().on_animation_frame();
+                    dispatcher_frame.dispatch(GameEvent::Frame);
                 }
                 request_animation_frame(closure.borrow().as_ref().unwrap());
             }) as Box<dyn FnMut(f64)>)));
@@ -197,190 +255,132 @@ impl GameLoop {
             }
         }
 
-        // callback http request from JS
-
-        let _xhr = XmlHttpRequest::new().unwrap();
-        let _xhr_cloned = Rc::new(RefCell::new(_xhr.clone()));
-
-        {
-            let ref_game_cloned_http_request = Rc::clone(&ref_game);
-
-            let closure_http_request = Closure::wrap(Box::new( move |e: Event| {
-                let xhr_target = e.target().unwrap();
-                let xhr = xhr_target.dyn_ref::<XmlHttpRequest>().unwrap();
-                if xhr.ready_state() == 4 {
-                    match xhr.status(){
-                        Ok(200) => {
-                            match xhr.response_text() {
-                                Ok(Some(response_text)) => {
-                                    match serde_json::from_str::<GeminiResponseBody>(&response_text) {
-                                        Ok(parsed_response) => {
-                                            if let Some(candidate) = parsed_response.candidates.first() {
-                                                if let Some(part) = candidate.content.parts.first(){
-                                                    ref_game_cloned_http_request.borrow_mut().on_http_request(part.text.clone());
-                                                } else {
-                                                    ref_game_cloned_http_request.borrow_mut().on_http_request("Error: No candidates found".to_string());
-                                                }
-                                            } else {
-                                                log!("Error: No candidates found in resonse");
-                                                ref_game_cloned_http_request.borrow_mut().on_http_request("Error: No candidates found".to_string());
-                                            }
-                                        }
-                                        Err(e) => {
-                                            log!("JSON Parse Error: {:?}", e);
-                                            ref_game_cloned_http_request.borrow_mut().on_http_request(format!("Error parsing response: {}", e));
-                                        }
-                                    }
-                                }
-                                Ok(None) => {
-                                    log!("Error reading response text: {:?}", e);
-                                    ref_game_cloned_http_request.borrow_mut().on_http_request("Error: Empty response".to_string());
-                                }
-                                Err(e) => {
-                                    log!("Error getting HTTP status: {:?}", e);
-                                    ref_game_cloned_http_request.borrow_mut().on_http_request(format!("Network Error: {:?}", e));
-                                }
-                            }
-                        }
-                        Ok(status_code) => {
-                            log!("HTTP Error: Status {}", status_code);
-                            let error_text = xhr.response_text().unwrap_or(Some("Failed to get error details".to_string())).unwrap_or_default();
-                            log!("Error Response Body: {}", error_text);
-                            ref_game_cloned_http_request.borrow_mut().on_http_request(format!("HTTP Error: {}", status_code));
-                        }
-                        Err(e) => {
-                            log!("Error Response HTTP status: {:?}", e);
-                            ref_game_cloned_http_request.borrow_mut().on_http_request(format!("Network Error: {:?}", e));
-                        }
-                    }
-                }
-
-            }) as Box<dyn FnMut(_)>);
-
-            let event_target: &EventTarget = _xhr.as_ref();
-            event_target.add_event_listener_with_callback(
-                "readystatechange",
-                closure_http_request.as_ref().unchecked_ref()).unwrap();
-
-            closure_http_request.forget();
-        }
-
         // callback touch from JS
 
         {
-            let ref_game_cloned_touch = Rc::clone(&ref_game);
+            let dispatcher_touch = dispatcher.clone();
             let c = Closure::wrap(Box::new(move |e:MouseEvent| {
-                // Start Recognition
-                let _page_type = ref_game_cloned_touch.borrow().get_page_type();
-                match _page_type { 
-                    PageType::Input => {
-                        ref_recognition_cloned.borrow().start();
-                    },
-                    _ => {}
-                }
-                ref_game_cloned_touch.borrow_mut().on_click();
+                dispatcher_touch.dispatch(GameEvent::Click { x: e.offset_x() as f64, y: e.offset_y() as f64 });
             }) as Box<dyn FnMut(_)>);
 
-            let _document = window().unwrap().document().unwrap();
-            let _canvas = _document.get_element_by_id("canvas").unwrap();
-            _canvas.add_event_listener_with_callback(
-                "mousedown",
-                c.as_ref().unchecked_ref(),
-            ).unwrap();
+            let _result = ref_game.borrow().get_canvas()
+                .and_then(|canvas| Ok(canvas.add_event_listener_with_callback("mousedown", c.as_ref().unchecked_ref())?));
+            if let Err(e) = _result {
+                ref_game.borrow_mut().on_error(e);
+            }
             c.forget();
 
-            let ref_game_touch_textarea_cloned = Rc::clone(&ref_game);
-            let d = Closure::wrap(Box::new(move |e:MouseEvent| {
-                ref_game_touch_textarea_cloned.borrow_mut().on_click();
+            // the message box lies over the canvas below the read aloud button, and its offsets
+            // are not canvas coordinates: a click on it only continues
+            let dispatcher_touch_textarea = dispatcher.clone();
+            let d = Closure::wrap(Box::new(move |_e:MouseEvent| {
+                dispatcher_touch_textarea.dispatch(GameEvent::Command { command: Command::Continue });
             }) as Box<dyn FnMut(_)>);
-            let _document = window().unwrap().document().unwrap();
-            let _canvas = _document.get_element_by_id("mytextarea").unwrap();
-            _canvas.add_event_listener_with_callback(
-                "mousedown",
-                d.as_ref().unchecked_ref(),
-            ).unwrap();
+            let _result = ref_game.borrow().get_textarea()
+                .and_then(|textarea| Ok(textarea.add_event_listener_with_callback("mousedown", d.as_ref().unchecked_ref())?));
+            if let Err(e) = _result {
+                ref_game.borrow_mut().on_error(e);
+            }
             d.forget();
         }
 
         // callback Keydown Event from JS
 
         {
-            let ref_game_cloned_keydown = Rc::clone(&ref_game);
+            let dispatcher_keydown = dispatcher.clone();
 
             let keydown_closure = Closure::wrap(Box::new(move |e: KeyboardEvent| {
-                if e.key_code() == 13 {
-                    let _document = window().unwrap().document().unwrap();
-                    let _input = _document.get_element_by_id("input").unwrap();
-                    let _text = _input.dyn_into::<HtmlInputElement>().unwrap();
-                    let _input_text = sanitize(_text.value());
-
-                    let _page_type = ref_game_cloned_keydown.borrow().get_page_type();
-                    match _page_type {
-                        PageType::First => {
-                            if _input_text != "" {
-                                let api_endpoint = format!("{}{}", GEMINI_API_ENDPOINT.to_string(), _input_text);
-                                let _= ref_game_cloned_keydown.borrow_mut().set_api_endpoint(api_endpoint);
-                            }
-                            let _= _text.set_value("");
-                        },
-                        PageType::Input => {
-                            let _text = ref_game_cloned_keydown.borrow().create_prompt(_input_text);
-                            let _api_endpoint = ref_game_cloned_keydown.borrow().get_api_endpoint();
-                            let api_endpoint = format!("{}",_api_endpoint);
-                            let request_body = GeminiRequestBody {
-                                contents: vec![GeminiRequestContent {
-                                parts: vec![GeminiRequestPart {text: _text}],
-                                }],
-                            };
-
-                            let _= ref_game_cloned_keydown.borrow_mut().next_page();
-                            let payload = match serde_json::to_string(&request_body){
-                                Ok(json) => json,
-                                Err(e) => {
-                                log!("Failed to serialize request body: {}", e);
-                                return;
-                                }
-                            };
-                            match _xhr_cloned.borrow().open("POST", &api_endpoint) {
-                                Ok(_) => {
-                                    if let Err(e) = _xhr_cloned.borrow().set_request_header("Content-Type","application/json"){
-                                        log!("Failed to set Context-Type header: {:?}", e);
-                                        return;
-                                    }
-                                    match _xhr_cloned.borrow().send_with_opt_str(Some(&payload)) {
-                                        Ok(_) => {
-                                            log!("Request sent successfully.");
-                                        }
-                                        Err(e) => log!("Failed to send request: {:?}", e),
-                                    }
-                                },
-                                Err(e) => {
-                                    log!("Failed to open XHR request: {:?}", e);
-                                }
-                            }
-                        },
-                        _ => {},
+                // Enter also ends an IME conversion (Japanese input)
+                if e.is_composing() { return; }
+                let _key = e.key();
+                // a key held down is pressed once, only the arrows move on
+                if e.repeat() && !_key.starts_with("Arrow") { return; }
+                if _key == "Enter" {
+                    let _input = dispatcher_keydown.game.borrow().get_input();
+                    match _input {
+                        Ok(_text) => dispatcher_keydown.dispatch(GameEvent::Enter { text: sanitize(_text.value()) }),
+                        Err(e) => dispatcher_keydown.game.borrow_mut().on_error(e),
                     }
-                    //ref_game_keypress_cloned.borrow_mut().on_click();
+                    return;
+                }
+                // letters and Space typed in the answer box are text, not commands
+                let _typing = e.target()
+                    .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+                    .is_some_and(|input| !input.disabled());
+                if _typing && _key.chars().count() == 1 { return; }
+                if e.ctrl_key() || e.meta_key() || e.alt_key() { return; }
+                if let Some(_command) = Command::from_key(&_key) {
+                    e.prevent_default();
+                    dispatcher_keydown.dispatch(GameEvent::Command { command: _command });
                 }
             }) as Box<dyn FnMut(_)>);
-            let _document = window().unwrap().document().unwrap();
-
-            let body = _document.body().unwrap();
-            body.add_event_listener_with_callback(
-                "keydown",
-                keydown_closure.as_ref().unchecked_ref(),
-            ).unwrap();
+            let _result = ref_game.borrow().get_document().body()
+                .ok_or_else(|| GameError::ElementMissing(String::from("body")))
+                .and_then(|body| Ok(body.add_event_listener_with_callback("keydown", keydown_closure.as_ref().unchecked_ref())?));
+            if let Err(e) = _result {
+                ref_game.borrow_mut().on_error(e);
+            }
             keydown_closure.forget();
         }
 
+        // load story from JS
+
+        {
+            let ref_game_cloned_story = Rc::clone(&ref_game);
+            let _dispatcher_settings = dispatcher.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let _stories = load_stories().await;
+                let mut _game = ref_game_cloned_story.borrow_mut();
+                _game.set_stories(_stories);
+
+                // saved session from localStorage
+
+                if let Some(_snapshot) = load_snapshot() {
+                    _game.restore(_snapshot);
+                }
+                if let Some(_api_key) = load_api_key() {
+                    _game.restore_api_key(_api_key);
+                }
+
+                // backend selection from the query string: ?backend=ollama&endpoint=...&model=...
+
+                if let Some(_settings) = judge_settings_from_location() {
+                    log!("Judge: {} {} {}", _settings.backend.name(), _settings.endpoint, _settings.model);
+                    _game.set_judge_settings(_settings);
+                }
+
+                // retry mode, speech and narration from the query string: ?retry=1&pass=80&attempts=3&lang=en-GB&narrate=1&rate=0.8
+
+                let _settings = settings_from_location(_game.get_settings());
+                _dispatcher_settings.configure_speech(&_settings.speech);
+                _game.set_settings(_settings);
+
+                // ?replay=1: replay the event log kept in localStorage (e.g. pasted from a bug report)
+
+                if query_params().is_some_and(|p| p.has("replay")) && let Some(_events) = load_event_log() {
+                    _game.replay(&_events);
+                }
+
+                // inputs from here on are kept in localStorage for bug reports
+
+                _game.start_recording();
+            });
+        }
+
         // callback image load from JS
 
         {
             let ref_game_cloned_image = Rc::clone(&ref_game);
 
             wasm_bindgen_futures::spawn_local(async move {
-                let _image = HtmlImageElement::new().unwrap();
+                let _image = match HtmlImageElement::new() {
+                    Ok(image) => image,
+                    Err(e) => {
+                        ref_game.borrow_mut().on_error(GameError::from(e));
+                        return;
+                    }
+                };
                 let f = Closure::once(Box::new(|| {
                     log!("IMAGE LOAD...");
                 }));
@@ -391,8 +391,280 @@ impl GameLoop {
                 let _result = wasm_bindgen_futures::JsFuture::from(_image.decode()).await;
 
                 ref_game_cloned_image.borrow_mut().on_image(_image);
-                ref_game.borrow().draw();
+                ref_game.borrow_mut().on_animation_frame();
             });
         }
     }
-}
\ No newline at end of file
+}
+
+// every input goes through the dispatcher; it runs the effects the game leaves to the loop
+
+struct Dispatcher<G: StaticGame + 'static> {
+    game: Rc<RefCell<G>>,
+    recognition: Rc<Option<WebkitSpeechRecognition>>,   // None: keyboard only
+    pending: Rc<RefCell<Option<AbortController>>>,  // judge request in flight
+}
+
+impl<G: StaticGame + 'static> Clone for Dispatcher<G> {
+    fn clone(&self) -> Self {
+        Dispatcher {
+            game: Rc::clone(&self.game),
+            recognition: Rc::clone(&self.recognition),
+            pending: Rc::clone(&self.pending),
+        }
+    }
+}
+
+impl<G: StaticGame + 'static> Dispatcher<G> {
+    fn dispatch(&self, event: GameEvent) {
+        let _effects = self.game.borrow_mut().dispatch(event);
+        for _effect in _effects {
+            match _effect {
+                Effect::StartRecognition => {
+                    let _result = self.recognition.as_ref().as_ref().map(|r| r.start());
+                    // not started: no end event follows, the error puts the mike back
+                    if let Some(Err(e)) = _result {
+                        let _name = js_sys::Reflect::get(&e, &JsValue::from_str("name")).ok().and_then(|n| n.as_string()).unwrap_or_default();
+                        log!("Speech recognition not started: {}", GameError::from(e));
+                        let _error = match _name.as_str() {
+                            "NotAllowedError" => String::from("not-allowed"),
+                            "" => String::from("start-failed"),
+                            _ => _name,
+                        };
+                        self.dispatch(GameEvent::SpeechError { error: _error });
+                    }
+                },
+                Effect::StopRecognition => {
+                    if let Some(_recognition) = self.recognition.as_ref() {
+                        _recognition.stop();
+                    }
+                },
+                Effect::ConfigureSpeech(speech) => self.configure_speech(&speech),
+                Effect::Speak { text, narration, sentence } => self.speak(&text, narration, sentence),
+                Effect::CancelSpeech => {
+                    if let Some(_synthesis) = window().and_then(|w| w.speech_synthesis().ok()) {
+                        _synthesis.cancel();
+                    }
+                },
+                Effect::Judge { prompt } => self.judge(prompt),
+                Effect::CancelJudge => {
+                    if let Some(_controller) = self.pending.borrow_mut().take() {
+                        _controller.abort();
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn listen(&self, event: &str, callback: &Closure<dyn FnMut(JsValue)>) {
+        if let Some(_recognition) = self.recognition.as_ref() {
+            _recognition.add_event_listener(event, callback);
+        }
+    }
+
+    // takes effect from the next start()
+
+    fn configure_speech(&self, speech: &SpeechSettings) {
+        let _recognition = match self.recognition.as_ref() {
+            Some(recognition) => recognition,
+            None => return,
+        };
+        _recognition.set_lang(&speech.lang);
+        _recognition.set_continuous(speech.continuous); // true: Continue if speech is interrupted
+        _recognition.set_interim_results(true); // true: Get intermediate results
+        _recognition.set_max_alternatives(speech.max_alternatives.clamp(1, MAX_ALTERNATIVES));
+    }
+
+    // one utterance per sentence; the end of a chapter sentence asks for the next one
+
+    fn speak(&self, text: &str, narration: u32, sentence: Option<usize>) {
+        let _synthesis = match window().and_then(|w| w.speech_synthesis().ok()) {
+            Some(synthesis) => synthesis,
+            None => {
+                log!("Speech synthesis is not available");
+                return;
+            }
+        };
+        let _utterance = match SpeechSynthesisUtterance::new_with_text(text) {
+            Ok(utterance) => utterance,
+            Err(e) => {
+                self.game.borrow_mut().on_error(GameError::from(e));
+                return;
+            }
+        };
+        let _settings = self.game.borrow().get_settings();
+        _utterance.set_lang(&_settings.speech.lang);
+        _utterance.set_rate(_settings.narration.rate.clamp(0.1, 10.0) as f32);
+        _utterance.set_pitch(_settings.narration.pitch.clamp(0.0, 2.0) as f32);
+        if !_settings.narration.voice.is_empty() {
+            let _voice = _synthesis.get_voices().iter()
+                .filter_map(|v| v.dyn_into::<SpeechSynthesisVoice>().ok())
+                .find(|v| v.name() == _settings.narration.voice);
+            _utterance.set_voice(_voice.as_ref());
+        }
+        if let Some(_sentence) = sentence {
+            let _dispatcher = self.clone();
+            let _on_end = Closure::once_into_js(move || {
+                _dispatcher.dispatch(GameEvent::NarrationEnd { narration, sentence: _sentence });
+            });
+            _utterance.set_onend(Some(_on_end.unchecked_ref()));
+        }
+        _synthesis.speak(&_utterance);
+    }
+
+    fn judge(&self, prompt: String) {
+        let _judge = match self.game.borrow().get_judge() {
+            Some(judge) => judge,
+            None => return,
+        };
+
+        // a new answer cancels the request still in flight
+        if let Some(_controller) = self.pending.borrow_mut().take() {
+            _controller.abort();
+        }
+        let _controller = AbortController::new().ok();
+        let _signal = _controller.as_ref().map(|c| c.signal());
+        self.pending.replace(_controller);
+
+        let _settings = self.game.borrow().get_settings();
+        let _dispatcher = self.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let _result = if _settings.stream {
+                http::judge_stream(_judge.as_ref(), &prompt, _signal.as_ref(), &_settings, |chunk| {
+                    _dispatcher.dispatch(GameEvent::ResponseChunk { text: chunk.to_string() });
+                }).await
+            } else {
+                http::judge(_judge.as_ref(), &prompt, _signal.as_ref(), &_settings).await
+            };
+            match _result {
+                Err(HttpError::Aborted) => {
+                    log!("Request cancelled");
+                },
+                _result => {
+                    if let Err(e) = &_result {
+                        log!("{}", e);
+                    }
+                    _dispatcher.dispatch(GameEvent::Response { result: _result.map_err(GameError::from) });
+                }
+            }
+        });
+    }
+}
+
+// SpeechRecognition, or the prefixed webkitSpeechRecognition; None when the browser has neither
+
+fn new_recognition() -> Option<WebkitSpeechRecognition> {
+    let _window = window()?;
+    let _class = ["SpeechRecognition", "webkitSpeechRecognition"].iter()
+        .filter_map(|name| js_sys::Reflect::get(&_window, &JsValue::from_str(name)).ok())
+        .find(|class| class.is_function())?;
+    js_sys::Reflect::construct(_class.unchecked_ref::<js_sys::Function>(), &js_sys::Array::new()).ok()
+        .map(|recognition| recognition.unchecked_into::<WebkitSpeechRecognition>())
+}
+
+// stories listed in the catalog; empty when the catalog cannot be loaded
+
+async fn load_stories() -> Vec<Story> {
+    let _catalog = match http::get_text(CATALOG_URL).await {
+        Ok(_json) => match Catalog::from_json(&_json) {
+            Ok(_catalog) => _catalog,
+            Err(e) => {
+                log!("Failed to parse catalog {}: {}", CATALOG_URL, e);
+                return Vec::new();
+            }
+        },
+        Err(e) => {
+            log!("Failed to load catalog {}: {}", CATALOG_URL, e);
+            return Vec::new();
+        }
+    };
+    let mut _stories = Vec::new();
+    for _url in _catalog.urls() {
+        match load_story(&_url).await {
+            Ok(_story) => _stories.push(_story),
+            Err(e) => log!("Failed to load story {}: {}", _url, e),
+        }
+    }
+    _stories
+}
+
+async fn load_story(url: &str) -> error::Result<Story> {
+    let _json = http::get_text(url).await?;
+    Ok(Story::from_json(&_json)?)
+}
+
+// judge settings from the page URL, None when no backend is given
+
+fn query_params() -> Option<UrlSearchParams> {
+    let _search = window()?.location().search().ok()?;
+    UrlSearchParams::new_with_str(&_search).ok()
+}
+
+fn judge_settings_from_location() -> Option<JudgeSettings> {
+    let _params = query_params()?;
+    let mut _settings = JudgeSettings::new(Backend::parse(&_params.get("backend")?)?);
+    if let Some(_endpoint) = _params.get("endpoint") {
+        _settings.endpoint = _endpoint;
+    }
+    if let Some(_model) = _params.get("model") {
+        _settings.model = _model;
+    }
+    Some(_settings)
+}
+
+// settings from the page URL, current values for missing ones
+
+fn settings_from_location(settings: Settings) -> Settings {
+    let mut _settings = settings;
+    let _params = match query_params() {
+        Some(p) => p,
+        None => return _settings,
+    };
+    if let Some(_retry) = _params.get("retry") {
+        _settings.retry_until_correct = matches!(_retry.as_str(), "1" | "true" | "on");
+    }
+    if let Some(_pass) = _params.get("pass").and_then(|p| p.parse::<u8>().ok()) {
+        _settings.pass_score = _pass.min(100);
+    }
+    if let Some(_attempts) = _params.get("attempts").and_then(|a| a.parse::<usize>().ok()) {
+        _settings.max_attempts = _attempts.max(1);
+    }
+    if let Some(_stream) = _params.get("stream") {
+        _settings.stream = matches!(_stream.as_str(), "1" | "true" | "on");
+    }
+    if let Some(_timeout) = _params.get("timeout").and_then(|t| t.parse::<u32>().ok()) {
+        _settings.timeout_ms = _timeout.saturating_mul(1000);
+    }
+    if let Some(_retries) = _params.get("retries").and_then(|r| r.parse::<u32>().ok()) {
+        _settings.max_retries = _retries;
+    }
+    if let Some(_backoff) = _params.get("backoff").and_then(|b| b.parse::<u32>().ok()) {
+        _settings.backoff_ms = _backoff;
+    }
+    if let Some(_lang) = _params.get("lang").filter(|l| !l.is_empty()) {
+        _settings.speech.lang = _lang;
+    }
+    if let Some(_continuous) = _params.get("continuous") {
+        _settings.speech.continuous = matches!(_continuous.as_str(), "1" | "true" | "on");
+    }
+    if let Some(_alternatives) = _params.get("alternatives").and_then(|a| a.parse::<u32>().ok()) {
+        _settings.speech.max_alternatives = _alternatives.clamp(1, MAX_ALTERNATIVES);
+    }
+    if let Some(_pronounce) = _params.get("pronounce") {
+        _settings.pronunciation = matches!(_pronounce.as_str(), "1" | "true" | "on");
+    }
+    if let Some(_narrate) = _params.get("narrate") {
+        _settings.narration.auto = matches!(_narrate.as_str(), "1" | "true" | "on");
+    }
+    if let Some(_voice) = _params.get("voice") {
+        _settings.narration.voice = _voice;
+    }
+    if let Some(_rate) = _params.get("rate").and_then(|r| r.parse::<f64>().ok()) {
+        _settings.narration.rate = _rate.clamp(0.1, 10.0);
+    }
+    if let Some(_pitch) = _params.get("pitch").and_then(|p| p.parse::<f64>().ok()) {
+        _settings.narration.pitch = _pitch.clamp(0.0, 2.0);
+    }
+    _settings
+}
@@ -0,0 +1,103 @@
+// Chapter narration: the chapter lines cut into sentences, each spoken on its own
+// so the one being read can be highlighted on the canvas
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub line: usize,
+    pub start: usize,       // char index in the line
+    pub end: usize,         // char index after the last char
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sentence {
+    pub text: String,       // what is spoken, without the [____] blank
+    pub segments: Vec<Segment>,
+}
+
+// sentences end with . ! ? (and the closing quotes after them) or with an empty line
+
+pub fn sentences(lines: &[String]) -> Vec<Sentence> {
+    let mut _sentences = Vec::new();
+    let mut _current = Sentence { text: String::new(), segments: Vec::new() };
+
+    for (_line, _text) in lines.iter().enumerate() {
+        let _chars: Vec<char> = _text.chars().collect();
+        if _chars.iter().all(|c| c.is_whitespace()) {
+            finish(&mut _sentences, &mut _current);
+            continue;
+        }
+        let mut _start = 0;
+        let mut i = 0;
+        while i < _chars.len() {
+            let mut _end = i + 1;
+            if matches!(_chars[i], '.' | '!' | '?') {
+                while _end < _chars.len() && matches!(_chars[_end], '\'' | '"' | '’' | '”') {
+                    _end += 1;
+                }
+                push(&mut _current, _line, &_chars, _start, _end);
+                finish(&mut _sentences, &mut _current);
+                _start = _end;
+            }
+            i = _end;
+        }
+        push(&mut _current, _line, &_chars, _start, _chars.len());
+    }
+    finish(&mut _sentences, &mut _current);
+    _sentences
+}
+
+fn push(sentence: &mut Sentence, line: usize, chars: &[char], start: usize, end: usize) {
+    // leading spaces are not highlighted
+    let _start = (start..end).find(|i| !chars[*i].is_whitespace()).unwrap_or(end);
+    if _start >= end { return; }
+    let _text: String = chars[_start..end].iter().filter(|c| !matches!(c, '[' | ']' | '_')).collect();
+    if _text.chars().any(|c| c.is_alphanumeric()) {
+        if !sentence.text.is_empty() { sentence.text.push(' '); }
+        sentence.text.push_str(_text.trim());
+    }
+    sentence.segments.push(Segment { line, start: _start, end });
+}
+
+fn finish(sentences: &mut Vec<Sentence>, sentence: &mut Sentence) {
+    if sentence.text.is_empty() {
+        sentence.segments.clear();
+        return;
+    }
+    sentences.push(std::mem::replace(sentence, Sentence { text: String::new(), segments: Vec::new() }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn sentences_end_with_punctuation_and_closing_quotes() {
+        let _sentences = sentences(&lines(&["\"Hello!\" she said. Then she", "  walked home", "", "The end"]));
+        let _texts: Vec<&str> = _sentences.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(_texts, vec!["\"Hello!\"", "she said.", "Then she walked home", "The end"]);
+        assert_eq!(_sentences[0].segments, vec![Segment { line: 0, start: 0, end: 8 }]);
+        // leading spaces are not highlighted
+        assert_eq!(_sentences[2].segments, vec![Segment { line: 0, start: 19, end: 27 }, Segment { line: 1, start: 2, end: 13 }]);
+    }
+
+    #[test]
+    fn blanks_are_highlighted_but_not_spoken() {
+        let _sentences = sentences(&lines(&["Say [____] to her."]));
+        assert_eq!(_sentences.len(), 1);
+        assert!(!_sentences[0].text.contains(['[', ']', '_']));
+        assert_eq!(_sentences[0].segments, vec![Segment { line: 0, start: 0, end: 18 }]);
+    }
+
+    #[test]
+    fn default_story_is_cut_into_sentences() {
+        let _story = crate::story::Story::default();
+        let _sentences = sentences(&_story.chapters[0].text);
+        assert!(_sentences.len() > 2);
+        assert_eq!(_sentences[0].segments[0], Segment { line: 0, start: 0, end: 56 });
+        assert!(_sentences.iter().all(|s| !s.text.contains('_')));
+    }
+}
@@ -0,0 +1,194 @@
+use crate::judge::Grade;
+use crate::checker::normalize;
+
+// Pronunciation mode: the recognized transcript aligned word by word against the sentence read aloud
+
+pub const LOW_CONFIDENCE: f64 = 0.7;   // below this, recognized words are only counted half
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordResult {
+    Correct,
+    Unclear,                    // recognized, but with a low confidence
+    Mispronounced(String),      // what was heard instead
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    pub word: String,           // as written in the reference
+    pub result: WordResult,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pronunciation {
+    pub reference: String,
+    pub transcript: String,
+    pub confidence: f64,        // 0.0 when the recognizer gives none
+    pub words: Vec<Word>,
+    pub extra: Vec<String>,     // heard but not in the reference
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Match(usize, usize),        // reference token, heard token
+    Substitute(usize, usize),
+    Delete(usize),              // reference token not heard
+    Insert(usize),              // heard token not in the reference
+}
+
+impl Pronunciation {
+    // 0 - 100, unclear words count half
+
+    pub fn score(&self) -> u8 {
+        if self.words.is_empty() { return 0; }
+        let _points: f64 = self.words.iter().map(|w| match w.result {
+            WordResult::Correct => 1.0,
+            WordResult::Unclear => 0.5,
+            _ => 0.0,
+        }).sum();
+        (100.0 * _points / self.words.len() as f64).round() as u8
+    }
+
+    pub fn grade(&self, pass_score: u8) -> Grade {
+        let _score = self.score();
+        let mut _lines = vec![String::from(if _score >= pass_score { "Well pronounced." } else { "Listen and try again." })];
+        let _mispronounced: Vec<String> = self.words.iter().filter_map(|w| match &w.result {
+            WordResult::Mispronounced(heard) => Some(format!("{} ({})", w.word, heard)),
+            _ => None,
+        }).collect();
+        let _skipped: Vec<&str> = self.words.iter().filter(|w| w.result == WordResult::Skipped).map(|w| w.word.as_str()).collect();
+        if !_mispronounced.is_empty() {
+            _lines.push(format!("Mispronounced: {}", _mispronounced.join(", ")));
+        }
+        if !_skipped.is_empty() {
+            _lines.push(format!("Skipped: {}", _skipped.join(", ")));
+        }
+        Grade {
+            correct: _score >= pass_score,
+            score: _score,
+            corrected_sentence: self.reference.clone(),
+            explanation: _lines.join("\n"),
+            missing: Vec::new(),
+        }
+    }
+}
+
+// aligns the normalized words (contractions expanded) with the least edits;
+// a written word is correct when all of its normalized words were heard
+
+pub fn align(reference: &str, transcript: &str, confidence: f64) -> Pronunciation {
+    let _written: Vec<&str> = reference.split_whitespace().collect();
+    let mut _tokens = Vec::new();
+    let mut _owner = Vec::new();
+    for (i, _word) in _written.iter().enumerate() {
+        for _token in normalize(_word) {
+            _tokens.push(_token);
+            _owner.push(i);
+        }
+    }
+    let _heard = normalize(transcript);
+
+    let mut _results: Vec<Vec<Step>> = vec![Vec::new(); _written.len()];
+    let mut _extra = Vec::new();
+    for _step in steps(&_tokens, &_heard) {
+        match _step {
+            Step::Match(r, _) | Step::Substitute(r, _) | Step::Delete(r) => _results[_owner[r]].push(_step),
+            Step::Insert(h) => _extra.push(_heard[h].clone()),
+        }
+    }
+
+    let _unclear = confidence > 0.0 && confidence < LOW_CONFIDENCE;
+    let _words = _written.iter().zip(_results.iter()).map(|(_word, _steps)| {
+        let _substituted: Vec<String> = _steps.iter().filter_map(|s| match s {
+            Step::Substitute(_, h) => Some(_heard[*h].clone()),
+            _ => None,
+        }).collect();
+        let _result = if _steps.is_empty() {
+            WordResult::Correct     // punctuation only
+        } else if _steps.iter().all(|s| matches!(s, Step::Delete(_))) {
+            WordResult::Skipped
+        } else if !_substituted.is_empty() || _steps.iter().any(|s| matches!(s, Step::Delete(_))) {
+            WordResult::Mispronounced(_substituted.join(" "))
+        } else if _unclear {
+            WordResult::Unclear
+        } else {
+            WordResult::Correct
+        };
+        Word { word: _word.to_string(), result: _result }
+    }).collect();
+
+    Pronunciation {
+        reference: reference.to_string(),
+        transcript: transcript.to_string(),
+        confidence,
+        words: _words,
+        extra: _extra,
+    }
+}
+
+// Levenshtein table over words, walked back from the end
+
+fn steps(reference: &[String], heard: &[String]) -> Vec<Step> {
+    let (_n, _m) = (reference.len(), heard.len());
+    let mut _table = vec![vec![0usize; _m + 1]; _n + 1];
+    for (i, _row) in _table.iter_mut().enumerate() { _row[0] = i; }
+    _table[0] = (0..=_m).collect();
+    for i in 1..=_n {
+        for j in 1..=_m {
+            let _cost = if reference[i - 1] == heard[j - 1] { 0 } else { 1 };
+            _table[i][j] = (_table[i - 1][j - 1] + _cost).min(_table[i - 1][j] + 1).min(_table[i][j - 1] + 1);
+        }
+    }
+
+    // ties go to a match, then a skipped word, then an extra word, then a substitution
+    let mut _steps = Vec::new();
+    let (mut i, mut j) = (_n, _m);
+    while i > 0 || j > 0 {
+        let _diagonal = i > 0 && j > 0;
+        if _diagonal && reference[i - 1] == heard[j - 1] && _table[i][j] == _table[i - 1][j - 1] {
+            _steps.push(Step::Match(i - 1, j - 1));
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && _table[i][j] == _table[i - 1][j] + 1 {
+            _steps.push(Step::Delete(i - 1));
+            i -= 1;
+        } else if j > 0 && _table[i][j] == _table[i][j - 1] + 1 {
+            _steps.push(Step::Insert(j - 1));
+            j -= 1;
+        } else {
+            _steps.push(Step::Substitute(i - 1, j - 1));
+            i -= 1;
+            j -= 1;
+        }
+    }
+    _steps.reverse();
+    _steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn align_marks_every_word_of_the_sentence() {
+        let _pronunciation = align("Don't forget to say good morning.", "do not for get to good morning", 0.9);
+        let _results: Vec<&WordResult> = _pronunciation.words.iter().map(|w| &w.result).collect();
+        assert_eq!(_results, vec![
+            &WordResult::Correct,
+            &WordResult::Mispronounced(String::from("for")),
+            &WordResult::Correct,
+            &WordResult::Skipped,
+            &WordResult::Correct,
+            &WordResult::Correct,
+        ]);
+        assert_eq!(_pronunciation.extra, vec![String::from("get")]);
+        assert_eq!(_pronunciation.score(), 67);
+        assert!(!_pronunciation.grade(80).correct);
+        assert!(_pronunciation.grade(60).correct);
+    }
+
+    #[test]
+    fn confidence_scales_the_score() {
+        assert_eq!(align("Good morning", "good morning", 0.5).score(), 50);
+    }
+}
@@ -0,0 +1,625 @@
+use web_sys::{CanvasRenderingContext2d, HtmlImageElement};
+use crate::common::*;
+use crate::story::*;
+use crate::session::*;
+use crate::engine::{Engine, Recognizer};
+use crate::error::GameError;
+use crate::narration::Segment;
+use crate::pronunciation::*;
+
+// Drawing of the canvas pages, independent of where the commands go
+
+pub trait Renderer {
+    fn clear(&mut self);
+    fn set_fill_style(&mut self, color: &str);
+    fn set_font(&mut self, font: &str);
+    fn set_text_align(&mut self, align: &str);
+    fn set_global_alpha(&mut self, alpha: f64);
+    fn fill_text(&mut self, text: &str, x: f64, y: f64);
+    fn draw_image(&mut self, source: [f64; 4], dest: [f64; 4]);    // region of the sprite sheet: [x, y, width, height]
+    fn measure_text(&self, text: &str) -> f64;                  // width in the current font
+}
+
+// Canvas size and position in the page
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Screen {
+    pub width: f64,
+    pub height: f64,
+    pub top: f64,
+    pub left: f64,
+}
+
+// sprite sheet regions
+
+const MIKE_OFF: [f64; 4] = [0.0, 900.0, 60.0, 150.0];
+const MIKE_ON: [f64; 4] = [60.0, 900.0, 60.0, 150.0];
+const MIKE_OPENING: [f64; 4] = [120.0, 900.0, 60.0, 150.0];
+const BORDER: [f64; 4] = [125.0, 150.0, 90.0, 50.0];
+
+pub struct CanvasRenderer<'a> {
+    context: &'a CanvasRenderingContext2d,
+    image: &'a HtmlImageElement,
+}
+
+impl<'a> CanvasRenderer<'a> {
+    pub fn new(context: &'a CanvasRenderingContext2d, image: &'a HtmlImageElement) -> Self {
+        CanvasRenderer { context, image }
+    }
+}
+
+impl Renderer for CanvasRenderer<'_> {
+    fn clear(&mut self) {
+        self.context.clear_rect(0.0, 0.0, 1000.0, 1000.0);
+    }
+    fn set_fill_style(&mut self, color: &str) {
+        self.context.set_fill_style_str(color);
+    }
+    fn set_font(&mut self, font: &str) {
+        self.context.set_font(font);
+    }
+    fn set_text_align(&mut self, align: &str) {
+        self.context.set_text_align(align);
+    }
+    fn set_global_alpha(&mut self, alpha: f64) {
+        self.context.set_global_alpha(alpha);
+    }
+    fn fill_text(&mut self, text: &str, x: f64, y: f64) {
+        let _= self.context.fill_text(text, x, y);
+    }
+    fn draw_image(&mut self, source: [f64; 4], dest: [f64; 4]) {
+        let (_s, _d) = (source, dest);
+        let _= self.context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+            self.image, _s[0], _s[1], _s[2], _s[3], _d[0], _d[1], _d[2], _d[3]);
+    }
+    fn measure_text(&self, text: &str) -> f64 {
+        self.context.measure_text(text).map(|m| m.width()).unwrap_or(0.0)
+    }
+}
+
+// the page of the engine's state
+
+pub fn draw_page(engine: &Engine, renderer: &mut impl Renderer, screen: &Screen) {
+    let _state = engine.state();
+    let _chapter = _state.chapter().unwrap_or(0);
+    let _story = engine.story();
+    let _center = screen.width / 2.0;
+    let _mike = [_center - 120.0 / 2.0, 210.0, 120.0, 300.0];
+
+    match _state {
+
+        // Story Picker
+
+        PageState::StoryPicker => {
+            // Title
+            renderer.set_fill_style(DEFAULT_COLOR);
+            renderer.set_font("36px MyFont");
+            renderer.set_text_align("center");
+            renderer.fill_text(TEXT_PICKER_TITLE, _center, 90.0);
+            // Stories, the row chosen with the arrow keys highlighted
+            let _color = |row: usize| if engine.cursor() == Some(row) { HIGHLIGHT_COLOR } else { DEFAULT_COLOR };
+            renderer.set_font("24px MyFont");
+            for (i, _s) in engine.stories().iter().enumerate() {
+                renderer.set_fill_style(_color(i));
+                renderer.fill_text(&format!("{}. {}", i + 1, _s.title), _center, PICKER_TOP + PICKER_ROW * i as f64);
+            }
+            // Forget API Key
+            if engine.has_api_key() {
+                renderer.set_font("18px MyFont");
+                let _row = engine.stories().len() + 1;
+                renderer.set_fill_style(_color(_row));
+                renderer.fill_text(TEXT_FORGET_KEY, _center, PICKER_TOP + PICKER_ROW * _row as f64);
+            }
+            // Speech Language
+            renderer.set_font("18px MyFont");
+            let _row = engine.stories().len() + 2;
+            let _language = format!("[ {}: {} ]", TEXT_LANGUAGE, engine.settings().speech.language_name());
+            renderer.set_fill_style(_color(_row));
+            renderer.fill_text(&_language, _center, PICKER_TOP + PICKER_ROW * _row as f64);
+            renderer.set_fill_style(DEFAULT_COLOR);
+        },
+
+        // Opening
+
+        PageState::ApiKeyEntry => {
+            // Title
+            renderer.set_fill_style(DEFAULT_COLOR);
+            renderer.set_font("36px MyFont");
+            renderer.set_text_align("center");
+            renderer.fill_text(&_story.title, _center, 90.0);
+            renderer.set_font("18px MyFont");
+            // Intro
+            for (i, _line) in _story.intro.iter().enumerate() {
+                renderer.fill_text(_line, _center, 142.0 + (LINE_SPACE * i) as f64);
+            }
+            // Mike
+            if engine.speech_available() {
+                renderer.draw_image(MIKE_OPENING, _mike);
+            }
+            // Cover Illustrations
+            for _sprite in _story.cover.iter() {
+                draw_sprite(renderer, _sprite);
+            }
+        },
+
+        // Finish
+
+        PageState::Finished => {
+            // Title
+            renderer.set_fill_style(DEFAULT_COLOR);
+            renderer.set_font("36px MyFont");
+            renderer.set_text_align("center");
+            renderer.fill_text(&_story.title, _center, 90.0);
+            for (i, _line) in TEXT_FIN.split('\n').enumerate() {
+                renderer.fill_text(_line, _center, 140.0 + (TEXT_SPACE * i) as f64);
+            }
+            // Scoreboard
+            draw_scoreboard(renderer, engine.session(), 210.0);
+        },
+
+        // Contents
+
+        PageState::ChapterPrompt { .. } | PageState::AwaitingResponse { .. } | PageState::Feedback { .. } => {
+
+            let _prompt = matches!(_state, PageState::ChapterPrompt { .. });
+
+            let _contents = match _story.chapter(_chapter) {
+                Some(c) => c,
+                None => return,
+            };
+
+            // Gray Disaplay
+            if !_prompt { renderer.set_global_alpha(0.3); }
+
+            // Title
+            renderer.set_font("18px Hiragino Sans");
+            renderer.set_fill_style(DEFAULT_COLOR);
+            renderer.set_text_align("center");
+            renderer.fill_text(&format!("【 {} 】", _story.title), _center, 30.0);
+
+            // CHAPTER, the sentence read aloud highlighted
+            renderer.set_text_align("left");
+            let _narrating = engine.narrating();
+            for (i, _line) in _contents.text.iter().enumerate() {
+                let _y = 70.0 + (LINE_SPACE * i) as f64;
+                match _narrating.as_ref().and_then(|n| n.segments.iter().find(|s| s.line == i)) {
+                    Some(_segment) => draw_highlighted(renderer, _line, _segment, 10.0, _y),
+                    None => renderer.fill_text(_line, 10.0, _y),
+                }
+            }
+
+            // Illustration
+            if let Some(_sprite) = &_contents.sprite {
+                draw_sprite(renderer, _sprite);
+            }
+
+            // Mike: on while listening, dimmed while the result is coming
+            renderer.set_global_alpha(0.5);
+            match engine.recognizer() {
+                _ if !engine.speech_available() => {},
+                Recognizer::Listening => renderer.draw_image(MIKE_ON, _mike),
+                Recognizer::Processing => {
+                    renderer.set_global_alpha(0.3);
+                    renderer.draw_image(MIKE_ON, _mike);
+                    renderer.set_global_alpha(1.0);
+                },
+                Recognizer::Idle | Recognizer::Error(_) => {
+                    renderer.set_global_alpha(0.3);
+                    renderer.draw_image(MIKE_OFF, _mike);
+                    renderer.set_global_alpha(1.0);
+                },
+            }
+
+            // pronunciation mode: the sentence to read
+            if let (true, Some(_target)) = (_prompt, engine.target()) {
+                renderer.set_global_alpha(1.0);
+                renderer.set_font("18px MyFont");
+                renderer.set_text_align("center");
+                renderer.set_fill_style(HIGHLIGHT_COLOR);
+                renderer.fill_text(&format!("{}: {}", TEXT_TARGET, _target), _center, 610.0);
+                renderer.set_fill_style(DEFAULT_COLOR);
+            }
+
+            // what the recognizer hears, faded while tentative
+            if let (true, Some(_heard)) = (_prompt, engine.heard()) {
+                renderer.set_global_alpha(if _heard.tentative { 0.5 } else { 1.0 });
+                renderer.set_font("18px MyFont");
+                renderer.set_text_align("center");
+                let _confidence = if _heard.confidence > 0.0 {
+                    format!("{} {:.0}%", TEXT_CONFIDENCE, _heard.confidence * 100.0)
+                } else {
+                    String::from(TEXT_LISTENING)
+                };
+                renderer.fill_text(&_confidence, _center, 540.0);
+                // alternatives to choose from, the chosen one bright
+                if _heard.alternatives.len() > 1 {
+                    renderer.set_font("16px MyFont");
+                    for (i, _alternative) in _heard.alternatives.iter().enumerate() {
+                        renderer.set_global_alpha(if _alternative.text == _heard.text { 1.0 } else { 0.5 });
+                        let _choice = format!("{}. {} ({:.0}%)", i + 1, _alternative.text, _alternative.confidence * 100.0);
+                        renderer.fill_text(&_choice, _center, ALTERNATIVES_TOP + ALTERNATIVES_ROW * i as f64);
+                    }
+                }
+                renderer.set_global_alpha(1.0);
+            } else if _prompt && !engine.speech_available() {
+                renderer.set_global_alpha(1.0);
+                renderer.set_font("18px MyFont");
+                renderer.set_text_align("center");
+                renderer.fill_text(TEXT_KEYBOARD_ONLY, _center, 540.0);
+            } else if let (true, Some(_message)) = (_prompt, engine.recognizer().message()) {
+                // state of the recognizer, errors in red
+                renderer.set_global_alpha(1.0);
+                renderer.set_font("18px MyFont");
+                renderer.set_text_align("center");
+                if let Recognizer::Error(_) = engine.recognizer() { renderer.set_fill_style(MISSED_COLOR); }
+                renderer.fill_text(&_message, _center, 540.0);
+                renderer.set_fill_style(DEFAULT_COLOR);
+            }
+
+            // border of the message from AI
+            if !_prompt {
+                renderer.set_global_alpha(1.0);
+                renderer.draw_image(BORDER, [screen.left + 10.0, screen.top + 40.0, screen.width - 20.0, screen.height - 100.0]);
+            }
+
+            // pronunciation of each word, instead of the message box
+            if let (false, Some(_pronunciation)) = (_prompt, engine.pronunciation()) {
+                draw_pronunciation(renderer, _pronunciation, screen);
+            }
+
+            // Read aloud: the chapter, or the corrected answer once it may be shown
+            if _prompt || (engine.grade().is_some_and(|g| !g.corrected_sentence.is_empty()) && !engine.must_retry()) {
+                renderer.set_global_alpha(1.0);
+                renderer.set_font("14px MyFont");
+                renderer.set_text_align("right");
+                renderer.fill_text(if _narrating.is_some() { TEXT_STOP_READING } else { TEXT_READ_ALOUD }, screen.width - 10.0, 30.0);
+                renderer.set_text_align("left");
+            }
+        }
+    }
+}
+
+// a line of the chapter with the part of the sentence being read in another color
+
+fn draw_highlighted(renderer: &mut impl Renderer, line: &str, segment: &Segment, x: f64, y: f64) {
+    let _chars: Vec<char> = line.chars().collect();
+    let _before: String = _chars[..segment.start].iter().collect();
+    let _sentence: String = _chars[segment.start..segment.end].iter().collect();
+    let _after: String = _chars[segment.end..].iter().collect();
+    let _x = x + renderer.measure_text(&_before);
+    if !_before.is_empty() { renderer.fill_text(&_before, x, y); }
+    renderer.set_fill_style(HIGHLIGHT_COLOR);
+    renderer.fill_text(&_sentence, _x, y);
+    renderer.set_fill_style(DEFAULT_COLOR);
+    let _x_after = _x + renderer.measure_text(&_sentence);
+    if !_after.trim().is_empty() { renderer.fill_text(&_after, _x_after, y); }
+}
+
+// the target sentence word by word: read well, unclear, mispronounced or skipped (faded)
+
+fn draw_pronunciation(renderer: &mut impl Renderer, pronunciation: &Pronunciation, screen: &Screen) {
+    let _center = screen.width / 2.0;
+    renderer.set_global_alpha(1.0);
+    renderer.set_text_align("center");
+    renderer.set_font("24px MyFont");
+    renderer.set_fill_style(DEFAULT_COLOR);
+    renderer.fill_text(&format!("{}  {} / 100", TEXT_PRONUNCIATION, pronunciation.score()), _center, 100.0);
+
+    renderer.set_text_align("left");
+    let (mut _x, mut _y) = (40.0, 160.0);
+    let _space = renderer.measure_text(" ");
+    for _word in pronunciation.words.iter() {
+        let _width = renderer.measure_text(&_word.word);
+        if _x + _width > screen.width - 40.0 && _x > 40.0 {
+            _x = 40.0;
+            _y += 36.0;
+        }
+        let (_color, _alpha) = match _word.result {
+            WordResult::Correct => (HIGHLIGHT_COLOR, 1.0),
+            WordResult::Unclear => (UNCLEAR_COLOR, 1.0),
+            WordResult::Mispronounced(_) => (MISSED_COLOR, 1.0),
+            WordResult::Skipped => (MISSED_COLOR, 0.5),
+        };
+        renderer.set_fill_style(_color);
+        renderer.set_global_alpha(_alpha);
+        renderer.fill_text(&_word.word, _x, _y);
+        _x += _width + _space;
+    }
+    renderer.set_global_alpha(1.0);
+    renderer.set_fill_style(DEFAULT_COLOR);
+
+    renderer.set_font("18px MyFont");
+    renderer.fill_text(&format!("{}: {}", TEXT_YOU_SAID, pronunciation.transcript), 40.0, _y + 60.0);
+    renderer.set_text_align("center");
+}
+
+// error screen: drawn instead of the page when it cannot be shown
+
+pub fn draw_error(renderer: &mut impl Renderer, error: &GameError, screen: &Screen) {
+    let _center = screen.width / 2.0;
+    renderer.set_global_alpha(1.0);
+    renderer.set_fill_style(DEFAULT_COLOR);
+    renderer.set_text_align("center");
+    renderer.set_font("36px MyFont");
+    renderer.fill_text(TEXT_ERROR_TITLE, _center, 90.0);
+    renderer.set_font("18px MyFont");
+    renderer.fill_text(&error.to_string(), _center, 150.0);
+    renderer.fill_text(TEXT_ERROR_RELOAD, _center, 150.0 + LINE_SPACE as f64);
+}
+
+// per chapter score, retries and time, then the total
+
+const SCOREBOARD_COLUMNS: [(f64, &str); 4] = [(100.0, "left"), (360.0, "right"), (450.0, "right"), (520.0, "right")];
+
+fn draw_scoreboard(renderer: &mut impl Renderer, session: &Session, top: f64) {
+    renderer.set_font("18px MyFont");
+    draw_row(renderer, top, [String::from("CHAPTER"), String::from("SCORE"), String::from("RETRY"), String::from("TIME")]);
+    for (i, _record) in session.chapters.iter().enumerate() {
+        draw_row(renderer, top + LINE_SPACE as f64 * (i + 1) as f64, [
+            format!("{}", _record.chapter),
+            _record.score().map(|s| s.to_string()).unwrap_or(String::from("-")),
+            _record.retries().to_string(),
+            format_time(_record.elapsed()),
+        ]);
+    }
+    draw_row(renderer, top + LINE_SPACE as f64 * (session.chapters.len() + 2) as f64, [
+        String::from("TOTAL"),
+        format!("{} / {}", session.total_score(), session.max_score()),
+        String::from(""),
+        format_time(session.elapsed()),
+    ]);
+    renderer.set_text_align("center");
+}
+
+fn draw_row(renderer: &mut impl Renderer, y: f64, cells: [String; 4]) {
+    for (_cell, (x, align)) in cells.iter().zip(SCOREBOARD_COLUMNS) {
+        renderer.set_text_align(align);
+        renderer.fill_text(_cell, x, y);
+    }
+}
+
+fn draw_sprite(renderer: &mut impl Renderer, sprite: &Sprite) {
+    renderer.draw_image(sprite.source, sprite.dest);
+}
+
+// Draw commands captured as data, for snapshot tests
+
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    Clear,
+    FillStyle(String),
+    Font(String),
+    TextAlign(String),
+    GlobalAlpha(f64),
+    FillText { text: String, x: f64, y: f64 },
+    DrawImage { source: [f64; 4], dest: [f64; 4] },
+}
+
+#[cfg(test)]
+impl std::fmt::Display for DrawCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DrawCommand::Clear => write!(f, "clear"),
+            DrawCommand::FillStyle(color) => write!(f, "fill_style {}", color),
+            DrawCommand::Font(font) => write!(f, "font {}", font),
+            DrawCommand::TextAlign(align) => write!(f, "text_align {}", align),
+            DrawCommand::GlobalAlpha(alpha) => write!(f, "global_alpha {}", alpha),
+            DrawCommand::FillText { text, x, y } => write!(f, "fill_text {:?} {} {}", text, x, y),
+            DrawCommand::DrawImage { source, dest } => write!(f, "draw_image {:?} {:?}", source, dest),
+        }
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct RecordingRenderer {
+    pub commands: Vec<DrawCommand>,
+}
+
+#[cfg(test)]
+impl RecordingRenderer {
+    pub fn new() -> Self {
+        RecordingRenderer::default()
+    }
+
+    // one command per line
+
+    pub fn dump(&self) -> String {
+        self.commands.iter().map(|c| format!("{}\n", c)).collect()
+    }
+}
+
+#[cfg(test)]
+impl Renderer for RecordingRenderer {
+    fn clear(&mut self) {
+        self.commands.push(DrawCommand::Clear);
+    }
+    fn set_fill_style(&mut self, color: &str) {
+        self.commands.push(DrawCommand::FillStyle(color.to_string()));
+    }
+    fn set_font(&mut self, font: &str) {
+        self.commands.push(DrawCommand::Font(font.to_string()));
+    }
+    fn set_text_align(&mut self, align: &str) {
+        self.commands.push(DrawCommand::TextAlign(align.to_string()));
+    }
+    fn set_global_alpha(&mut self, alpha: f64) {
+        self.commands.push(DrawCommand::GlobalAlpha(alpha));
+    }
+    fn fill_text(&mut self, text: &str, x: f64, y: f64) {
+        self.commands.push(DrawCommand::FillText { text: text.to_string(), x, y });
+    }
+    fn draw_image(&mut self, source: [f64; 4], dest: [f64; 4]) {
+        self.commands.push(DrawCommand::DrawImage { source, dest });
+    }
+    // fixed width per char, so snapshots do not depend on fonts
+    fn measure_text(&self, text: &str) -> f64 {
+        text.chars().count() as f64 * 9.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::judge::{Backend, Grade, JudgeSettings};
+    use crate::event::{Alternative, Command, GameEvent};
+
+    const SCREEN: Screen = Screen { width: 600.0, height: 640.0, top: 0.0, left: 0.0 };
+
+    // compares with src/snapshots/<name>.txt; UPDATE_SNAPSHOTS=1 cargo test rewrites the files
+
+    fn assert_snapshot(name: &str, engine: &Engine) {
+        let mut _renderer = RecordingRenderer::new();
+        draw_page(engine, &mut _renderer, &SCREEN);
+        let _actual = _renderer.dump();
+        let _path = format!("{}/src/snapshots/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&_path, &_actual).unwrap();
+            return;
+        }
+        let _expected = std::fs::read_to_string(&_path)
+            .unwrap_or_else(|_| panic!("missing snapshot {}, run with UPDATE_SNAPSHOTS=1", _path));
+        assert_eq!(_actual, _expected, "snapshot {} differs", name);
+    }
+
+    fn engine() -> Engine {
+        let mut _engine = Engine::new();
+        _engine.set_judge_settings(JudgeSettings::new(Backend::Offline));
+        _engine
+    }
+
+    fn chapter_one() -> Engine {
+        let mut _engine = engine();
+        _engine.select_story(0, 0.0);
+        _engine
+    }
+
+    #[test]
+    fn story_picker() {
+        let mut _engine = engine();
+        _engine.set_api_key(String::from("key"));
+        assert_snapshot("story_picker", &_engine);
+    }
+
+    #[test]
+    fn story_picker_cursor() {
+        let mut _engine = engine();
+        _engine.handle(&GameEvent::Command { command: Command::Up }, 0.0);
+        assert_snapshot("story_picker_cursor", &_engine);
+    }
+
+    #[test]
+    fn api_key_entry() {
+        let mut _engine = Engine::new();
+        _engine.select_story(0, 0.0);
+        assert_eq!(_engine.state(), PageState::ApiKeyEntry);
+        assert_snapshot("api_key_entry", &_engine);
+    }
+
+    #[test]
+    fn chapter_prompt() {
+        let mut _engine = chapter_one();
+        _engine.handle(&GameEvent::Click { x: 0.0, y: 0.0 }, 0.0);
+        assert_eq!(_engine.recognizer(), &Recognizer::Listening);
+        assert_snapshot("chapter_prompt", &_engine);
+    }
+
+    #[test]
+    fn interim_transcript() {
+        let mut _engine = chapter_one();
+        _engine.handle(&GameEvent::Click { x: 0.0, y: 0.0 }, 0.0);
+        _engine.handle(&GameEvent::InterimTranscript { text: String::from("Do not"), confidence: 0.62 }, 0.0);
+        assert_snapshot("interim_transcript", &_engine);
+    }
+
+    #[test]
+    fn speech_alternatives() {
+        let mut _engine = chapter_one();
+        _engine.handle(&GameEvent::Click { x: 0.0, y: 0.0 }, 0.0);
+        let _alternatives = vec![
+            Alternative { text: String::from("Do not for get"), confidence: 0.41 },
+            Alternative { text: String::from("Due not forget"), confidence: 0.2 },
+        ];
+        _engine.handle(&GameEvent::Transcript { text: String::from("Do not forget"), confidence: 0.87, alternatives: _alternatives }, 0.0);
+        _engine.choose_alternative(1);
+        assert_snapshot("speech_alternatives", &_engine);
+    }
+
+    #[test]
+    fn narration() {
+        let mut _engine = chapter_one();
+        _engine.handle(&GameEvent::Command { command: Command::ReadAloud }, 0.0);
+        _engine.handle(&GameEvent::NarrationEnd { narration: 1, sentence: 0 }, 0.0);
+        assert_snapshot("narration", &_engine);
+    }
+
+    #[test]
+    fn pronunciation() {
+        let mut _engine = chapter_one();
+        let mut _settings = _engine.settings().clone();
+        _settings.pronunciation = true;
+        _engine.set_settings(_settings);
+        _engine.handle(&GameEvent::Click { x: 0.0, y: 0.0 }, 0.0);
+        _engine.handle(&GameEvent::Transcript { text: String::from("Do not for get to good morning"), confidence: 0.9, alternatives: Vec::new() }, 0.0);
+        _engine.handle(&GameEvent::SpeechEnd, 0.0);
+        assert_snapshot("pronunciation", &_engine);
+    }
+
+    #[test]
+    fn microphone_blocked() {
+        let mut _engine = chapter_one();
+        _engine.handle(&GameEvent::Click { x: 0.0, y: 0.0 }, 0.0);
+        _engine.handle(&GameEvent::SpeechError { error: String::from("not-allowed") }, 0.0);
+        _engine.handle(&GameEvent::SpeechEnd, 0.0);
+        assert_snapshot("microphone_blocked", &_engine);
+    }
+
+    #[test]
+    fn keyboard_only() {
+        let mut _engine = chapter_one();
+        _engine.set_speech_available(false);
+        assert_snapshot("keyboard_only", &_engine);
+    }
+
+    #[test]
+    fn awaiting_response() {
+        let mut _engine = chapter_one();
+        _engine.submit_answer("answer", 0.0);
+        assert_snapshot("awaiting_response", &_engine);
+    }
+
+    #[test]
+    fn feedback() {
+        let mut _engine = chapter_one();
+        _engine.submit_answer("answer", 0.0);
+        let _grade = Grade { correct: false, score: 40, corrected_sentence: String::new(), explanation: String::new(), missing: Vec::new() };
+        _engine.receive_response(Ok(_grade), 1000.0);
+        assert_snapshot("feedback", &_engine);
+    }
+
+    #[test]
+    fn finished() {
+        let mut _engine = chapter_one();
+        let mut _now = 0.0;
+        while let Some(_chapter) = _engine.state().chapter() {
+            let _answer = _engine.story().chapter(_chapter).unwrap().answers[0].clone();
+            let _grade = _engine.check_answer(&_answer).unwrap();
+            _engine.submit_answer(&_answer, _now);
+            _engine.receive_response(Ok(_grade), _now);
+            _now += 75_000.0;
+            _engine.click(0.0, _now);
+        }
+        assert_eq!(_engine.state(), PageState::Finished);
+        assert_snapshot("finished", &_engine);
+    }
+
+    #[test]
+    fn error_screen() {
+        let mut _renderer = RecordingRenderer::new();
+        draw_error(&mut _renderer, &GameError::ElementMissing(String::from("input")), &SCREEN);
+        assert!(_renderer.commands.contains(&DrawCommand::FillText {
+            text: String::from("Element #input is missing from the page"), x: 300.0, y: 150.0,
+        }));
+    }
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use crate::judge::Grade;
+
+// Record of one play through a story; times are milliseconds (Date.now())
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attempt {
+    pub answer: String,
+    pub grade: Option<Grade>,   // None until judged, or when the judge failed
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChapterRecord {
+    pub chapter: usize,
+    pub attempts: Vec<Attempt>,
+    pub started: f64,
+    pub finished: Option<f64>,
+}
+
+impl ChapterRecord {
+    pub fn retries(&self) -> usize {
+        self.attempts.len().saturating_sub(1)
+    }
+
+    // score of the last judged attempt
+
+    pub fn score(&self) -> Option<u8> {
+        self.attempts.iter().rev().find_map(|a| a.grade.as_ref()).map(|g| g.score)
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.finished.map(|f| f - self.started).unwrap_or(0.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub story: String,
+    pub started: f64,
+    pub finished: Option<f64>,
+    pub chapters: Vec<ChapterRecord>,
+}
+
+impl Session {
+    pub fn new(story: &str, now: f64) -> Self {
+        Session {
+            story: story.to_string(),
+            started: now,
+            finished: None,
+            chapters: Vec::new(),
+        }
+    }
+
+    pub fn record(&self, chapter: usize) -> Option<&ChapterRecord> {
+        self.chapters.iter().find(|r| r.chapter == chapter)
+    }
+
+    fn record_mut(&mut self, chapter: usize) -> Option<&mut ChapterRecord> {
+        self.chapters.iter_mut().find(|r| r.chapter == chapter)
+    }
+
+    pub fn start_chapter(&mut self, chapter: usize, now: f64) {
+        if self.record(chapter).is_none() {
+            self.chapters.push(ChapterRecord { chapter, attempts: Vec::new(), started: now, finished: None });
+        }
+    }
+
+    pub fn record_answer(&mut self, chapter: usize, answer: &str) {
+        if let Some(_record) = self.record_mut(chapter) {
+            _record.attempts.push(Attempt { answer: answer.to_string(), grade: None });
+        }
+    }
+
+    pub fn record_grade(&mut self, chapter: usize, grade: Option<Grade>) {
+        if let Some(_attempt) = self.record_mut(chapter).and_then(|r| r.attempts.last_mut()) {
+            _attempt.grade = grade;
+        }
+    }
+
+    pub fn finish_chapter(&mut self, chapter: usize, now: f64) {
+        if let Some(_record) = self.record_mut(chapter) {
+            _record.finished = Some(now);
+        }
+    }
+
+    pub fn finish(&mut self, now: f64) {
+        self.finished = Some(now);
+    }
+
+    pub fn total_score(&self) -> u32 {
+        self.chapters.iter().filter_map(|r| r.score()).map(u32::from).sum()
+    }
+
+    pub fn max_score(&self) -> u32 {
+        self.chapters.len() as u32 * 100
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.finished.map(|f| f - self.started).unwrap_or(0.0)
+    }
+}
+
+// milliseconds -> m:ss
+
+pub fn format_time(millis: f64) -> String {
+    let _seconds = (millis / 1000.0).max(0.0) as u64;
+    format!("{}:{:02}", _seconds / 60, _seconds % 60)
+}
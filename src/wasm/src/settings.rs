@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+// Player / teacher settings
+
+pub const PASS_SCORE: u8 = 80;
+pub const MAX_ATTEMPTS: usize = 3;
+pub const TIMEOUT_MS: u32 = 30_000;
+pub const MAX_RETRIES: u32 = 3;
+pub const BACKOFF_MS: u32 = 1_000;
+pub const MAX_BACKOFF_MS: u32 = 30_000;
+
+// languages offered for speech recognition: BCP 47 tag, name on the picker
+
+pub const LANGUAGES: [(&str, &str); 8] = [
+    ("en-US", "English (US)"),
+    ("en-GB", "English (UK)"),
+    ("en-AU", "English (Australia)"),
+    ("en-IN", "English (India)"),
+    ("fr-FR", "French"),
+    ("de-DE", "German"),
+    ("es-ES", "Spanish"),
+    ("ja-JP", "Japanese"),
+];
+pub const MAX_ALTERNATIVES: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeechSettings {
+    pub lang: String,
+    pub continuous: bool,           // keep listening after a pause
+    pub max_alternatives: u32,      // 1 - MAX_ALTERNATIVES
+}
+
+impl SpeechSettings {
+    pub fn language_name(&self) -> &str {
+        LANGUAGES.iter().find(|(tag, _)| *tag == self.lang).map(|(_, name)| *name).unwrap_or(&self.lang)
+    }
+
+    // next language of LANGUAGES, the first one after an unlisted tag
+
+    pub fn next_language(&mut self) {
+        let _next = LANGUAGES.iter().position(|(tag, _)| *tag == self.lang).map(|i| (i + 1) % LANGUAGES.len()).unwrap_or(0);
+        self.lang = LANGUAGES[_next].0.to_string();
+    }
+}
+
+impl Default for SpeechSettings {
+    fn default() -> Self {
+        SpeechSettings {
+            lang: LANGUAGES[0].0.to_string(),
+            continuous: false,
+            max_alternatives: 3,
+        }
+    }
+}
+
+// text-to-speech of the chapters and of the corrected answer
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NarrationSettings {
+    pub auto: bool,                 // read each chapter and corrected answer when shown
+    pub voice: String,              // SpeechSynthesisVoice.name, "" for the default voice of the language
+    pub rate: f64,                  // 0.1 - 10
+    pub pitch: f64,                 // 0 - 2
+}
+
+impl Default for NarrationSettings {
+    fn default() -> Self {
+        NarrationSettings {
+            auto: false,
+            voice: String::new(),
+            rate: 1.0,
+            pitch: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub retry_until_correct: bool,  // stay on a chapter until the grade reaches pass_score
+    pub pass_score: u8,
+    pub max_attempts: usize,        // then show the answer and continue
+    pub stream: bool,               // show the answer as it is generated, when the backend streams
+    pub timeout_ms: u32,            // per HTTP attempt
+    pub max_retries: u32,           // for 429 / 5xx / network errors
+    pub backoff_ms: u32,            // first retry delay, doubled each retry
+    pub speech: SpeechSettings,
+    pub narration: NarrationSettings,
+    pub pronunciation: bool,        // read the model answer aloud instead of translating
+}
+
+impl Settings {
+
+    // delay before retry number `retry` (1 origin)
+
+    pub fn backoff(&self, retry: u32) -> u32 {
+        let _factor = 1u32.checked_shl(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.backoff_ms.saturating_mul(_factor).min(MAX_BACKOFF_MS)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            retry_until_correct: false,
+            pass_score: PASS_SCORE,
+            max_attempts: MAX_ATTEMPTS,
+            stream: true,
+            timeout_ms: TIMEOUT_MS,
+            max_retries: MAX_RETRIES,
+            backoff_ms: BACKOFF_MS,
+            speech: SpeechSettings::default(),
+            narration: NarrationSettings::default(),
+            pronunciation: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let _settings = Settings { backoff_ms: 1000, ..Settings::default() };
+        let _delays: Vec<u32> = (1..=4).map(|retry| _settings.backoff(retry)).collect();
+        assert_eq!(_delays, vec![1000, 2000, 4000, 8000]);
+        assert_eq!(_settings.backoff(40), MAX_BACKOFF_MS);
+        assert_eq!(Settings { backoff_ms: u32::MAX, ..Settings::default() }.backoff(2), MAX_BACKOFF_MS);
+    }
+}
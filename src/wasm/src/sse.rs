@@ -0,0 +1,89 @@
+// Incremental server-sent events parser: feed bytes as they arrive, get the data of each completed event
+
+#[derive(Debug, Default)]
+pub struct SseParser {
+    bytes: Vec<u8>,     // undecoded tail (a UTF-8 sequence split across chunks)
+    buffer: String,     // decoded text of the event in progress
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        SseParser::default()
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.bytes.extend_from_slice(chunk);
+        let _valid = match std::str::from_utf8(&self.bytes) {
+            Ok(_) => self.bytes.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        let _text = String::from_utf8_lossy(&self.bytes[.._valid]).replace("\r\n", "\n");
+        self.buffer.push_str(&_text);
+        self.bytes.drain(.._valid);
+
+        let mut _events = Vec::new();
+        while let Some(_end) = self.buffer.find("\n\n") {
+            let _event: String = self.buffer.drain(.._end + 2).collect();
+            if let Some(_data) = event_data(&_event) {
+                _events.push(_data);
+            }
+        }
+        _events
+    }
+
+    // data of an event left without the closing blank line
+
+    pub fn finish(&mut self) -> Option<String> {
+        let _event = std::mem::take(&mut self.buffer);
+        event_data(&_event)
+    }
+}
+
+fn event_data(event: &str) -> Option<String> {
+    let _lines: Vec<&str> = event.lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+    if _lines.is_empty() { None } else { Some(_lines.join("\n")) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_complete_at_the_blank_line() {
+        let mut _parser = SseParser::new();
+        assert!(_parser.push(b"data: {\"a\":").is_empty());
+        assert_eq!(_parser.push(b"1}\n\ndata: two\n"), vec![String::from("{\"a\":1}")]);
+        assert_eq!(_parser.push(b"\n"), vec![String::from("two")]);
+    }
+
+    #[test]
+    fn crlf_lines_and_multiline_data() {
+        let mut _parser = SseParser::new();
+        let _events = _parser.push(b": comment\r\nevent: message\r\ndata: one\r\ndata:two\r\n\r\n");
+        assert_eq!(_events, vec![String::from("one\ntwo")]);
+        assert!(_parser.push(b"event: ping\r\n\r\n").is_empty());
+    }
+
+    #[test]
+    fn utf8_split_across_chunks() {
+        let _bytes = "data: おはよう\n\n".as_bytes();
+        let mut _parser = SseParser::new();
+        let mut _events = Vec::new();
+        // one byte at a time cuts every multi-byte character
+        for _byte in _bytes {
+            _events.extend(_parser.push(&[*_byte]));
+        }
+        assert_eq!(_events, vec![String::from("おはよう")]);
+    }
+
+    #[test]
+    fn finish_returns_the_last_event_without_blank_line() {
+        let mut _parser = SseParser::new();
+        assert!(_parser.push(b"data: last").is_empty());
+        assert_eq!(_parser.finish(), Some(String::from("last")));
+        assert_eq!(_parser.finish(), None);
+    }
+}
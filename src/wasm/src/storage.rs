@@ -0,0 +1,55 @@
+use web_sys::{window, Storage};
+use crate::engine::Snapshot;
+use crate::event::EventLog;
+use crate::judge::ApiKey;
+
+// Session saved to window.localStorage; the API key is kept in its own entry so it can be forgotten alone
+
+pub const SNAPSHOT_KEY: &str = "adventure.snapshot";
+pub const API_KEY_KEY: &str = "adventure.api_key";
+pub const EVENT_LOG_KEY: &str = "adventure.event_log";   // for bug reports: copy and replay with event::replay
+
+fn local_storage() -> Option<Storage> {
+    window()?.local_storage().ok()?
+}
+
+pub fn load_snapshot() -> Option<Snapshot> {
+    let _json = local_storage()?.get_item(SNAPSHOT_KEY).ok()??;
+    serde_json::from_str::<Snapshot>(&_json).ok()
+}
+
+pub fn save_snapshot(snapshot: &Snapshot) {
+    if let (Some(_storage), Ok(_json)) = (local_storage(), serde_json::to_string(snapshot)) {
+        let _= _storage.set_item(SNAPSHOT_KEY, &_json);
+    }
+}
+
+// a bare key saved by an older version does not say where it belongs: it is not loaded
+
+pub fn load_api_key() -> Option<ApiKey> {
+    let _json = local_storage()?.get_item(API_KEY_KEY).ok()??;
+    serde_json::from_str::<ApiKey>(&_json).ok()
+}
+
+pub fn save_api_key(api_key: &ApiKey) {
+    if let (Some(_storage), Ok(_json)) = (local_storage(), serde_json::to_string(api_key)) {
+        let _= _storage.set_item(API_KEY_KEY, &_json);
+    }
+}
+
+pub fn forget_api_key() {
+    if let Some(_storage) = local_storage() {
+        let _= _storage.remove_item(API_KEY_KEY);
+    }
+}
+
+pub fn save_event_log(events: &EventLog) {
+    if let (Some(_storage), Ok(_json)) = (local_storage(), events.to_json()) {
+        let _= _storage.set_item(EVENT_LOG_KEY, &_json);
+    }
+}
+
+pub fn load_event_log() -> Option<EventLog> {
+    let _json = local_storage()?.get_item(EVENT_LOG_KEY).ok()??;
+    EventLog::from_json(&_json).ok()
+}
@@ -0,0 +1,73 @@
+use serde::Deserialize;
+
+// Story shipped with the crate, used until (or unless) the served catalog is loaded
+
+pub const DEFAULT_STORY: &str = include_str!("../../../stories/little_red_riding_hood.json");
+pub const STORY_DIR: &str = "stories/";
+pub const CATALOG_URL: &str = "stories/catalog.json";
+
+// Region of the sprite sheet (source) and where it is drawn on the canvas (dest): [x, y, width, height]
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Sprite {
+    pub source: [f64; 4],
+    pub dest: [f64; 4],
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub text: Vec<String>,      // lines of the chapter, with the [____] blank to translate
+    pub blank: String,          // sentence the player translates into English
+    #[serde(default)]
+    pub answers: Vec<String>,   // expected English answers
+    #[serde(default)]
+    pub sprite: Option<Sprite>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Story {
+    pub title: String,
+    #[serde(default)]
+    pub intro: Vec<String>,
+    #[serde(default)]
+    pub cover: Vec<Sprite>,     // illustrations on the opening page
+    pub chapters: Vec<Chapter>,
+}
+
+// Story files (relative to STORY_DIR) offered on the picker page
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Catalog {
+    pub stories: Vec<String>,
+}
+
+impl Catalog {
+    pub fn from_json(json: &str) -> Result<Catalog, serde_json::Error> {
+        serde_json::from_str::<Catalog>(json)
+    }
+
+    pub fn urls(&self) -> Vec<String> {
+        self.stories.iter().map(|s| format!("{}{}", STORY_DIR, s)).collect()
+    }
+}
+
+impl Story {
+    pub fn from_json(json: &str) -> Result<Story, serde_json::Error> {
+        serde_json::from_str::<Story>(json)
+    }
+
+    // chapter: 1 origin
+
+    pub fn chapter(&self, chapter: usize) -> Option<&Chapter> {
+        match chapter {
+            0 => None,
+            _ => self.chapters.get(chapter - 1),
+        }
+    }
+}
+
+impl Default for Story {
+    fn default() -> Self {
+        Story::from_json(DEFAULT_STORY).expect("default story should be valid")
+    }
+}